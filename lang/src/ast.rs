@@ -0,0 +1,283 @@
+use crate::{
+  seq_types::Trigger,
+  scales::Key
+};
+
+pub type Span = std::ops::Range<usize>;
+
+//what's inside a bar or a group
+pub type Steps = Vec<Node<Step>>;
+
+//a name and the value it gets
+pub type Assign = (Node<String>,Node<Expr>);
+
+//every bit of the tree knows where it came from in the source
+#[derive(Debug,Clone,PartialEq)]
+pub struct Node<T> {
+  pub item:T,
+  pub span:Span
+}
+
+impl<T> Node<T> {
+  pub fn new(item:T,span:Span) -> Self {
+    Self {
+      item,
+      span
+    }
+  }
+}
+
+//the numbers keywords take are expressions, they get checked when they run
+#[derive(Debug,Clone,PartialEq)]
+pub enum Stmt {
+  Assign(Node<String>,Node<Expr>),
+  SlotAssign(Node<Expr>,Node<Expr>),
+  //bpm 140 over 16, the bars are there when it's a ramp
+  Bpm(Node<Expr>,Option<Node<Expr>>),
+  Tap,
+  Div(Node<Target>,Node<Expr>),
+  Sync(Node<Target>,Node<Expr>),
+  Play(Node<Target>),
+  Stop(Node<Target>),
+  ListOuts,
+  OpenOut(Node<Expr>,Node<Expr>),
+  SetOut(Node<Target>,Node<Expr>),
+  FindLps,
+  OpenLp(Node<Expr>),
+  //clock_out 1 on, the output number and whether it sends clock
+  ClockOut(Node<Expr>,Node<bool>),
+  ListIns,
+  //clock_in 2 follows the clock on input 2, clock_in off goes back to our own
+  ClockIn(Option<Node<Expr>>),
+  ClockStats,
+  //the whole song, apart from play and stop on single slots
+  Pause,
+  Continue,
+  Restart,
+  //locate 8 goes to the start of bar 8, counting from 0
+  Locate(Node<Expr>),
+  DoFile(Node<String>),
+  Key(Node<Key>),
+  //def name(params) = body, the params are only set while the body runs
+  Def(Node<String>,Vec<Node<String>>,Node<Expr>),
+  //slot kick = 0, slot names live apart from other names
+  SlotName(Node<String>,Node<Expr>),
+  //for name in range or list { statements }, the body runs once for each thing
+  For(Node<String>,Node<Expr>,Vec<Node<Stmt>>)
+}
+
+//the slots a keyword works on, "all" or a list like "1,3,5" where
+//each one can be a number, a range or a list
+#[derive(Debug,Clone,PartialEq)]
+pub enum Target {
+  All,
+  These(Vec<Node<Expr>>)
+}
+
+#[derive(Debug,Clone,PartialEq)]
+pub enum Expr {
+  Trigger(Trigger),
+  Bar(Steps),
+  //the assignments are only seen inside the sequence
+  Seq(Vec<Assign>,Vec<Node<Steps>>),
+  Name(String),
+  Call(Node<String>,Vec<Node<Expr>>),
+  //a * on a bar or a trigger repeats it, on numbers it multiplies
+  Bin(Op,Box<Node<Expr>>,Box<Node<Expr>>),
+  Neg(Box<Node<Expr>>),
+  //a..b counts from a to b, both ends included
+  Range(Box<Node<Expr>>,Box<Node<Expr>>),
+  Num(f64),
+  Str(String)
+}
+
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub enum Op {
+  Add,
+  Sub,
+  Mul,
+  Div
+}
+
+impl Stmt {
+  //every expression right under the statement
+  pub fn exprs(&self) -> Vec<&Node<Expr>> {
+    match self {
+      Stmt::Assign(_,v) | Stmt::SlotName(_,v) | Stmt::Def(_,_,v) | Stmt::For(_,v,_) => vec![v],
+      Stmt::SlotAssign(a,b) | Stmt::OpenOut(a,b) | Stmt::Bpm(a,Some(b)) => vec![a,b],
+      Stmt::Div(t,b) | Stmt::Sync(t,b) | Stmt::SetOut(t,b) => t.item.exprs().into_iter().chain([b]).collect(),
+      Stmt::Play(t) | Stmt::Stop(t) => t.item.exprs(),
+      Stmt::Bpm(a,None) | Stmt::Locate(a) | Stmt::OpenLp(a) | Stmt::ClockOut(a,_) => vec![a],
+      Stmt::ClockIn(a) => a.iter().collect(),
+      Stmt::Tap | Stmt::Pause | Stmt::Continue | Stmt::Restart | Stmt::ListOuts | Stmt::ListIns | Stmt::ClockStats | Stmt::FindLps | Stmt::DoFile(_) | Stmt::Key(_) => vec![]
+    }
+  }
+}
+
+impl Target {
+  pub fn exprs(&self) -> Vec<&Node<Expr>> {
+    match self {
+      Target::All => vec![],
+      Target::These(es) => es.iter().collect()
+    }
+  }
+}
+
+#[derive(Debug,Clone,PartialEq)]
+pub enum Step {
+  Rest(usize),
+  //the target is a name or a function call
+  Hit {
+    target:Node<Expr>,
+    chance:Option<u8>,
+    ties:usize,
+    legato:bool,
+    reps:usize
+  },
+  Group {
+    count:usize,
+    steps:Steps,
+    reps:usize
+  }
+}
+
+//every name that gets looked up, with where it gets used
+pub fn name_refs(stmts:&[Node<Stmt>]) -> Vec<Node<String>> {
+  let mut out = vec![];
+  for s in stmts.iter() {
+    s.item.exprs().into_iter().for_each(|v|expr_refs(v,&mut out));
+    if let Stmt::For(_,_,body) = &s.item {
+      out.extend(name_refs(body));
+    }
+  }
+  out
+}
+
+fn expr_refs(e:&Node<Expr>,out:&mut Vec<Node<String>>) {
+  match &e.item {
+    Expr::Name(nm) => out.push(Node::new(nm.clone(),e.span.clone())),
+    Expr::Bar(steps) => step_refs(steps,out),
+    Expr::Seq(assigns,bars) => {
+      assigns.iter().for_each(|(_,v)|expr_refs(v,out));
+      bars.iter().for_each(|b|step_refs(&b.item,out));
+    },
+    Expr::Call(_,args) => args.iter().for_each(|a|expr_refs(a,out)),
+    Expr::Bin(_,a,b) | Expr::Range(a,b) => {
+      expr_refs(a,out);
+      expr_refs(b,out);
+    },
+    Expr::Neg(v) => expr_refs(v,out),
+    Expr::Trigger(_) | Expr::Num(_) | Expr::Str(_) => ()
+  }
+}
+
+fn step_refs(steps:&Steps,out:&mut Vec<Node<String>>) {
+  for s in steps.iter() {
+    match &s.item {
+      Step::Hit{target,..} => expr_refs(target,out),
+      Step::Group{steps,..} => step_refs(steps,out),
+      Step::Rest(_) => ()
+    }
+  }
+}
+
+//where the name used at a spot in the source got set. names set inside a
+//sequence win inside it, then the params of a def around it, otherwise
+//it's the last one set before the spot. a param points at its def and
+//a loop's name points at what it loops over
+pub fn definition<'a>(stmts:&'a [Node<Stmt>],nm:&str,at:usize) -> Option<(&'a Node<String>,&'a Node<Expr>)> {
+  let mut before = None;
+  let mut after = None;
+  for s in stmts.iter() {
+    if let Stmt::Assign(name,v) | Stmt::SlotName(name,v) = &s.item && name.item == nm {
+      match name.span.start <= at {
+        true => before = Some((name,v)),
+        false => after = after.or(Some((name,v)))
+      };
+    }
+
+    if let Some((name,v)) = s.item.exprs().into_iter().find_map(|v|seq_definition(v,nm,at)) {
+      return Some((name,v));
+    }
+
+    if let Stmt::Def(name,params,body) = &s.item && s.span.contains(&at) && params.iter().any(|p|p.item == nm) {
+      return Some((name,body));
+    }
+
+    if let Stmt::For(name,range,body) = &s.item && s.span.contains(&at) {
+      if name.item == nm {
+        return Some((name,range));
+      }
+      if let Some(found) = definition(body,nm,at) {
+        return Some(found);
+      }
+    }
+  }
+
+  before.or(after)
+}
+
+//the innermost sequence around the spot that sets the name
+fn seq_definition<'a>(e:&'a Node<Expr>,nm:&str,at:usize) -> Option<&'a Assign> {
+  if !e.span.contains(&at) {
+    return None;
+  }
+
+  match &e.item {
+    Expr::Seq(assigns,bars) => {
+      assigns.iter().find_map(|(_,v)|seq_definition(v,nm,at))
+      .or_else(||bars.iter().find_map(|b|steps_definition(&b.item,nm,at)))
+      .or_else(||assigns.iter().find(|(n,_)|n.item == nm))
+    },
+    Expr::Bar(steps) => steps_definition(steps,nm,at),
+    Expr::Call(_,args) => args.iter().find_map(|a|seq_definition(a,nm,at)),
+    Expr::Bin(_,a,b) | Expr::Range(a,b) => seq_definition(a,nm,at).or_else(||seq_definition(b,nm,at)),
+    Expr::Neg(v) => seq_definition(v,nm,at),
+    _ => None
+  }
+}
+
+fn steps_definition<'a>(steps:&'a Steps,nm:&str,at:usize) -> Option<&'a Assign> {
+  steps.iter().find_map(|s|match &s.item {
+    Step::Hit{target,..} => seq_definition(target,nm,at),
+    Step::Group{steps,..} => steps_definition(steps,nm,at),
+    Step::Rest(_) => None
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{Lexer,parse_fsm};
+
+  #[test]
+  fn test_definition() {
+    let src = "X = (C_1 100)\nb = [X -]\nX = (D_1 100)\ns = { X = (E_1 100)\n [X] }\nc = [X]";
+    let mut lx = Lexer::new(src);
+    let (tree,_) = parse_fsm::root(&mut lx);
+
+    let refs : Vec<_> = name_refs(&tree).into_iter().map(|r|(r.item,r.span.start)).collect();
+    assert_eq!(refs,[("X".to_string(),19),("X".to_string(),60),("X".to_string(),70)]);
+
+    let def_at = |at| definition(&tree,"X",at).map(|(nm,_)|nm.span.start);
+    assert_eq!(def_at(19),Some(0));
+    assert_eq!(def_at(60),Some(44));
+    assert_eq!(def_at(70),Some(24));
+    assert_eq!(definition(&tree,"nope",19),None);
+
+    //using it before it's set still finds it
+    let (tree,_) = parse_fsm::root(&mut Lexer::new("b = [X]\nX = (C_1 100)"));
+    assert_eq!(definition(&tree,"X",5).map(|(nm,_)|nm.span.start),Some(8));
+
+    //params belong to the def they're in
+    let (tree,_) = parse_fsm::root(&mut Lexer::new("a = (C_1 100)\ndef f(a) = [a -]\nb = [a]"));
+    assert_eq!(definition(&tree,"a",26).map(|(nm,_)|nm.span.start),Some(18));
+    assert_eq!(definition(&tree,"a",35).map(|(nm,_)|nm.span.start),Some(0));
+
+    //so do loop names, and names set in the loop
+    let (tree,_) = parse_fsm::root(&mut Lexer::new("for i in 0..3 {\n b = [X]\n play i; <i> = b\n}"));
+    assert_eq!(name_refs(&tree).len(),4);
+    assert_eq!(definition(&tree,"i",35).map(|(nm,_)|nm.span.start),Some(4));
+    assert_eq!(definition(&tree,"b",40).map(|(nm,_)|nm.span.start),Some(17));
+  }
+}
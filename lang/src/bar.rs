@@ -1,35 +1,59 @@
-use super::{Lexer,Token,Env,Runtime};
-use crate::seq_types::{Val,Trigger,LineBuilder};
+use super::{Lexer,Token,parse_fsm};
+use crate::ast::{Node,Step,Steps};
+use crate::err::ErrorKind::{self,Unexpected,BadSymbol,InputEnded,OutOfRange};
 
-pub fn parse<RT>(lx:&mut Lexer,env:&Env<RT>,line:&mut LineBuilder) -> Result<(),&'static str>
-where
-  RT:Runtime
-{
-  lx.expect(Token::OpenSq).map_err(|_|"somehow missed the '['")?;
+pub fn parse(lx:&mut Lexer) -> Result<Steps,ErrorKind> {
+  lx.expect(Token::OpenSq).map_err(|_|Unexpected("somehow missed the '['"))?;
+  steps(lx,Token::CloseSq)
+}
 
+//the insides of a bar or a group, up to and including the closing token
+fn steps(lx:&mut Lexer,end:Token) -> Result<Steps,ErrorKind> {
+  let mut out = vec![];
   loop {
-    match lx.lookahead() {
-      Token::CloseSq => {
+    let start = lx.lookahead_span().start;
+    let step = match lx.lookahead() {
+      t if t == end => {
         lx.advance();
         break;
       },
-      Token::Dash => bar_rests(lx,line),
-      Token::Sym | Token::Digits => bar_trigger(lx,env,line),
-      Token::Err => return Err("we couldn't figure this symbol out"),
-      Token::Eof => return Err("woah, the input ended"),
+      Token::Dash => bar_rests(lx),
+      Token::OpenParen => bar_group(lx),
+      Token::Sym | Token::Digits => bar_trigger(lx),
+      Token::Err => return Err(BadSymbol),
+      Token::Eof => return Err(InputEnded),
+      t if t.is_keyword() => {
+        lx.advance();
+        return Err(parse_fsm::keyword(lx));
+      },
       _ => {
         //you gotta step forward or else you will highlight the last good token
         lx.advance();
-        return Err("we are working on a bar, meaning we need a name or a - or ]")
+        return Err(Unexpected("we are working on a bar, meaning we need a name, a -, a group or the end of it"))
       }
     }?;
+
+    out.push(Node::new(step,start..lx.span_end()));
   };
 
-  Ok(())
+  Ok(out)
+}
+
+//(3: X X X) squeezes its steps into a single step of the outer bar,
+//the size gets checked against the steps when it runs
+fn bar_group(lx:&mut Lexer) -> Result<Step,ErrorKind> {
+  lx.expect(Token::OpenParen).map_err(|_|Unexpected("somehow missed the '('"))?;
+  lx.expect(Token::Digits).map_err(|_|Unexpected("a group needs to start with how many steps are in it, like (3: X X X)"))?;
+  let count = lx.slice().parse::<usize>().map_err(|_|OutOfRange("couldn't parse the size of this group"))?;
+  lx.expect(Token::Colon).map_err(|_|Unexpected("we need a ':' after the size of the group"))?;
+
+  let steps = steps(lx,Token::CloseParen)?;
+  let reps = parse_fsm::repeats(lx)?;
+  Ok(Step::Group{count,steps,reps})
 }
 
-fn bar_rests(lx:&mut Lexer,line:&mut LineBuilder) -> Result<(),&'static str> {
-  lx.expect(Token::Dash).map_err(|_|"somehow missed the first '-'")?;
+fn bar_rests(lx:&mut Lexer) -> Result<Step,ErrorKind> {
+  lx.expect(Token::Dash).map_err(|_|Unexpected("somehow missed the first '-'"))?;
   let mut count = 1;
 
   while lx.lookahead() == Token::Dash {
@@ -37,20 +61,17 @@ fn bar_rests(lx:&mut Lexer,line:&mut LineBuilder) -> Result<(),&'static str> {
     lx.advance();
   }
 
-  line.rests(count);
-  Ok(())
+  Ok(Step::Rest(count))
 }
 
-fn bar_trigger<RT>(lx:&mut Lexer,env:&Env<RT>,line:&mut LineBuilder) -> Result<(),&'static str> 
-where
-  RT:Runtime
-{
-  lx.advance();
-  let nm = lx.slice();
+fn bar_trigger(lx:&mut Lexer) -> Result<Step,ErrorKind> {
+  let start = lx.lookahead_span().start;
+  let target = parse_fsm::alias(lx)?;
+  let target = Node::new(target,start..lx.span_end());
 
-  let val = match env.lookup(nm) {
-    Some(v) => v,
-    None => return Err("we couldn't find a value with this name")
+  let chance = match lx.lookahead() {
+    Token::Quest => Some(parse_fsm::chance(lx)?),
+    _ => None
   };
   
   let mut ties = 0;
@@ -67,43 +88,35 @@ where
     false
   };
 
-  match val {
-    Val::Trigger(Trigger{nn,vel}) => line.trig(*nn,*vel,ties,legato),
-    Val::Bar(len,evs) => {
-      line.merge(*len,evs);
-      line.rests(ties);
-    }
-  };
-
-  Ok(())
+  let reps = parse_fsm::repeats(lx)?;
+  Ok(Step::Hit{target,chance,ties,legato,reps})
 }
 
 #[cfg(test)]
 mod tests{
-  use crate::test_utils;
-  use super::*;
+  use std::collections::HashMap;
+  use crate::{Env,test_utils};
+  use crate::seq_types::{Val,Trigger,Pitch,SeqNote};
+
+  fn bar(env:&mut Env<crate::NullRuntime>,input:&str) -> Result<(usize,HashMap<usize,Vec<SeqNote>>),crate::ErrorKind> {
+    match test_utils::eval_val(env,input)? {
+      Val::Bar(len,evs) => Ok((len,evs)),
+      _ => panic!("should be a bar")
+    }
+  }
 
   #[test]
   fn test_bar() {
-    let mut env = Env::new(test_utils::NullRt{});
-    env.set("X",Val::Trigger(Trigger{nn:1,vel:1}));
-
-    let mut lb = LineBuilder::default();
-    let input = "[X - - -]";
-    let mut lx = Lexer::new(input);
+    let mut env = Env::new(crate::NullRuntime);
+    env.set("X",Val::Trigger(Trigger{pitches:vec![Pitch::Note(1)],vel:1,prob:100}));
 
-    parse(&mut lx,&env,&mut lb).expect("shouldn't error");
-    let (len,evs) = lb.done();
+    let (len,evs) = bar(&mut env,"[X - - -]").expect("shouldn't error");
     assert_eq!(len,4);
     assert_eq!(evs.len(),1);
     assert_eq!(evs.get(&0).unwrap().len(),1);
     
     env.set("bounce",Val::Bar(len,evs));
-    let mut lb = LineBuilder::default();
-    let input = "[bounce bounce bounce bounce]";
-    let mut lx = Lexer::new(input);
-    parse(&mut lx,&env,&mut lb).expect("shouldn't error");
-    let (len,evs) = lb.done();
+    let (len,evs) = bar(&mut env,"[bounce bounce bounce bounce]").expect("shouldn't error");
     assert_eq!(len,16);
     assert_eq!(evs.len(),4);
     assert_eq!(evs.get(&0).unwrap().len(),1);
@@ -111,6 +124,50 @@ mod tests{
     assert_eq!(evs.get(&8).unwrap().len(),1);
     assert_eq!(evs.get(&12).unwrap().len(),1);
 
+    let (len,evs) = bar(&mut env,"[X?40 - X - bounce?50]").expect("shouldn't error");
+    assert_eq!(len,8);
+    assert_eq!(evs.get(&0).unwrap()[0].prob,40);
+    assert_eq!(evs.get(&2).unwrap()[0].prob,100);
+    assert_eq!(evs.get(&4).unwrap()[0].prob,50);
+
+    //a chance on a trigger that has its own multiplies, same as on a bar
+    env.set("Y",Val::Trigger(Trigger{pitches:vec![Pitch::Note(1)],vel:1,prob:40}));
+    let (_,evs) = bar(&mut env,"[Y?50 Y]").expect("shouldn't error");
+    assert_eq!(evs.get(&0).unwrap()[0].prob,20);
+    assert_eq!(evs.get(&1).unwrap()[0].prob,40);
+
+    assert!(bar(&mut env,"[X?101]").is_err());
+
+    let (len,evs) = bar(&mut env,"[euc(3,8,X) bounce]").expect("shouldn't error");
+    assert_eq!(len,12);
+    assert_eq!(evs.len(),4);
+    assert!(evs.contains_key(&3));
+    assert!(evs.contains_key(&8));
+
+    let (len,evs) = bar(&mut env,"[X - (3: X X X) -]").expect("shouldn't error");
+    assert_eq!(len,4);
+    let subs : Vec<_> = evs.get(&2).unwrap().iter().map(|n|n.sub).collect();
+    assert_eq!(subs,[(0,3),(1,3),(2,3)]);
+
+    let (len,evs) = bar(&mut env,"[(2: X (2: X X)) X]").expect("shouldn't error");
+    assert_eq!(len,2);
+    let subs : Vec<_> = evs.get(&0).unwrap().iter().map(|n|(n.sub,n.duration)).collect();
+    assert_eq!(subs,[((0,2),1),((2,4),1),((3,4),1)]);
+
+    assert!(bar(&mut env,"[X (3: X X) -]").is_err());
+
+    let (len,evs) = bar(&mut env,"[X=*2 bounce*3 (2: X X)*2]").expect("shouldn't error");
+    assert_eq!(len,18);
+    assert_eq!(evs.get(&0).unwrap()[0].duration,2);
+    assert_eq!(evs.get(&2).unwrap()[0].duration,2);
+    assert!(evs.contains_key(&4));
+    assert!(evs.contains_key(&8));
+    assert!(evs.contains_key(&12));
+    assert_eq!(evs.get(&16).unwrap().len(),2);
+    assert_eq!(evs.get(&17).unwrap().len(),2);
+
+    assert!(bar(&mut env,"[X*0]").is_err());
+
     //TODO::TEST ERROR CASES
   }
 }
@@ -1,16 +1,38 @@
-use std::collections::HashMap;
+use std::collections::{HashMap,BTreeSet};
+use std::path::{Path,PathBuf};
+use std::sync::Arc;
 
 use super::{
   Lexer,
   parse_fsm,
+  eval,
   Runtime,
+  Slots,
+  SLOT_COUNT,
   seq_types::{SeqNote,Val},
-  err
+  scales::Key,
+  ast::{Node,Expr},
+  err::{self,EvalError,ErrorKind}
 };
 
+//a def's param names and the body they get used in
+#[derive(Debug)]
+pub struct Def {
+  pub params:Vec<String>,
+  pub body:Node<Expr>
+}
+
 #[derive(Debug)]
 pub struct Env<T> {
   stack:Vec<HashMap<String,Val>>,
+  defs:HashMap<String,Arc<Def>>,
+  //slot names are kept apart so "kick" can be a trigger and a slot.
+  //filled is every slot that's had something put in it
+  slot_names:HashMap<String,usize>,
+  filled:BTreeSet<usize>,
+  //where paths start when they aren't in a file, and the files loading right now
+  dir:Option<PathBuf>,
+  loading:Vec<PathBuf>,
   rt:T
 }
 
@@ -18,10 +40,20 @@ impl<T:Runtime> Env<T> {
   pub fn new(rt:T) -> Self {
     Self {
       stack:vec![HashMap::new()],
+      defs:HashMap::new(),
+      slot_names:HashMap::new(),
+      filled:BTreeSet::new(),
+      dir:None,
+      loading:vec![],
       rt
     }
   }
 
+  //without one paths start from wherever sgv was run
+  pub fn set_dir(&mut self,dir:&Path) {
+    self.dir = Some(dir.to_path_buf());
+  }
+
   pub fn lookup(&self,nm:&str) -> Option<&Val> {
     for scope in self.stack.iter().rev() {
       if let Some(val) = scope.get(nm) {
@@ -31,6 +63,47 @@ impl<T:Runtime> Env<T> {
     None
   }
 
+  //the closest names to a misspelled one, from any scope we can see
+  pub fn similar(&self,nm:&str) -> Vec<String> {
+    let names = self.stack.iter().flat_map(|scope|scope.keys().map(|k|k.as_str()));
+    err::suggest(nm,names.chain(self.slot_names.keys().map(|k|k.as_str())))
+  }
+
+  pub fn slot_num(&self,nm:&str) -> Option<usize> {
+    self.slot_names.get(nm).copied()
+  }
+
+  pub fn name_slot(&mut self,nm:&str,slot:usize) {
+    self.slot_names.insert(String::from(nm),slot);
+    self.rt.name_slot(slot,nm);
+  }
+
+  //a name that's never been given a slot gets the first one nobody is using,
+  //None once every slot has a name or something in it
+  pub fn new_slot(&mut self,nm:&str) -> Option<usize> {
+    let taken : BTreeSet<usize> = self.slot_names.values().copied().chain(self.filled.iter().copied()).collect();
+    let slot = (0..SLOT_COUNT).find(|n|!taken.contains(n))?;
+    self.name_slot(nm,slot);
+    Some(slot)
+  }
+
+  pub fn define(&mut self,nm:&str,def:Def) {
+    self.defs.insert(String::from(nm),Arc::new(def));
+  }
+
+  pub fn def(&self,nm:&str) -> Option<Arc<Def>> {
+    self.defs.get(nm).cloned()
+  }
+
+  pub fn def_names(&self) -> impl Iterator<Item=&str> {
+    self.defs.keys().map(|k|k.as_str())
+  }
+
+  //how many scopes deep we are, the file itself is 1
+  pub fn depth(&self) -> usize {
+    self.stack.len()
+  }
+
   pub fn step_in(&mut self) {
     self.stack.push(HashMap::new())
   }
@@ -44,44 +117,80 @@ impl<T:Runtime> Env<T> {
     stack_top.insert(String::from(nm),val);
   }
 
-  pub fn do_file<P:AsRef<std::path::Path>>(&mut self,file:P) -> Result<(),err::ParseError> {
-    let ftxt = std::fs::read_to_string(file).map_err(|_|{
-      err::ParseError::Msg("couldn't open file")
+  //errors in the file point into the file, not at the line that loaded it.
+  //if it doesn't parse none of it runs. paths in a file start from its folder
+  pub fn do_file(&mut self,path:&Node<String>) -> Result<(),Vec<EvalError>> {
+    let full = match self.loading.last().and_then(|f|f.parent()).or(self.dir.as_deref()) {
+      Some(base) => base.join(&path.item),
+      None => PathBuf::from(&path.item)
+    };
+
+    let ftxt = std::fs::read_to_string(&full).map_err(|_|{
+      vec![path.err(ErrorKind::NoFile)]
     })?;
 
-    let mut sub_lex = Lexer::new(&ftxt[..]);
-    let parse_result = parse_fsm::root(&mut sub_lex,self);
-    let (_,start,end) = sub_lex.done();
+    //a file loading itself would go round forever
+    let full = full.canonicalize().unwrap_or(full);
+    if self.loading.contains(&full) {
+      return Err(vec![path.err(ErrorKind::FileLoop)]);
+    }
+
+    let in_file = |span,msg| EvalError::SubFile(path.item.clone(),ftxt.clone(),span,msg);
 
-    match parse_result {
-      Ok(_) => Ok(()),
-      Err(err::ParseError::Msg(msg)) => Err(err::ParseError::SubFile(msg,ftxt,start,end)),
-      Err(e@err::ParseError::SubFile(_,_,_,_)) => Err(e)
+    let mut sub_lex = Lexer::new(&ftxt[..]);
+    let (tree,errs) = parse_fsm::root(&mut sub_lex);
+    if !errs.is_empty() {
+      return Err(errs.into_iter().map(|e|in_file(e.span,e.item)).collect());
     }
+
+    self.loading.push(full);
+    let res = eval::run(&tree,self);
+    self.loading.pop();
+
+    res.map_err(|errs|{
+      errs.into_iter().map(|e|match e {
+        EvalError::At(msg,span) => in_file(span,msg),
+        e@EvalError::SubFile(_,_,_,_) => e
+      }).collect()
+    })
+  }
+
+  //for looking at what got asked of the runtime, like sgv check does
+  pub fn runtime(&self) -> &T {
+    &self.rt
   }
 
   pub fn assign_slot(&mut self,snum:usize,len:usize,evs:HashMap<usize,Vec<SeqNote>>) {
+    self.filled.insert(snum);
     self.rt.assign_slot(snum,len,evs);
   }
 
-  pub fn set_bpm(&mut self,bpm_num:usize) {
+  pub fn set_bpm(&mut self,bpm_num:f64) {
     self.rt.set_bpm(bpm_num);
   }
 
-  pub fn set_div(&mut self,slot:usize,div_num:usize) {
-    self.rt.set_div(slot,div_num);
+  pub fn ramp_bpm(&mut self,bpm_num:f64,bars:usize) {
+    self.rt.ramp_bpm(bpm_num,bars);
   }
 
-  pub fn set_sync(&mut self,slot:usize,sync_num:usize) {
-    self.rt.set_sync(slot,sync_num);
+  pub fn tap(&mut self) {
+    self.rt.tap();
   }
 
-  pub fn play_slot(&mut self,slot_num:usize) {
-    self.rt.play_slot(slot_num);
+  pub fn set_div(&mut self,slots:&Slots,div_num:usize) {
+    self.rt.set_div(slots,div_num);
   }
 
-  pub fn stop_slot(&mut self,slot_num:usize) {
-    self.rt.stop_slot(slot_num)
+  pub fn set_sync(&mut self,slots:&Slots,sync_num:usize) {
+    self.rt.set_sync(slots,sync_num);
+  }
+
+  pub fn play_slots(&mut self,slots:&Slots) {
+    self.rt.play_slots(slots);
+  }
+
+  pub fn stop_slots(&mut self,slots:&Slots) {
+    self.rt.stop_slots(slots)
   }
 
   pub fn list_outs(&mut self) {
@@ -96,11 +205,47 @@ impl<T:Runtime> Env<T> {
     self.rt.open_out(out_num,channel)
   }
 
-  pub fn set_output(&mut self,slot:usize,output:usize) {
-    self.rt.set_output(slot,output)
+  pub fn set_output(&mut self,slots:&Slots,output:usize) {
+    self.rt.set_output(slots,output)
   }
   
   pub fn open_lp(&mut self,lp_num:usize) {
     self.rt.open_lp(lp_num)
   }
+
+  pub fn clock_out(&mut self,out_num:usize,on:bool) {
+    self.rt.clock_out(out_num,on)
+  }
+
+  pub fn list_ins(&mut self) {
+    self.rt.list_ins()
+  }
+
+  pub fn clock_in(&mut self,in_num:Option<usize>) {
+    self.rt.clock_in(in_num)
+  }
+
+  pub fn clock_stats(&mut self) {
+    self.rt.clock_stats()
+  }
+
+  pub fn pause(&mut self) {
+    self.rt.pause()
+  }
+
+  pub fn resume(&mut self) {
+    self.rt.resume()
+  }
+
+  pub fn restart(&mut self) {
+    self.rt.restart()
+  }
+
+  pub fn locate(&mut self,bar:usize) {
+    self.rt.locate(bar)
+  }
+
+  pub fn set_key(&mut self,key:Key) {
+    self.rt.set_key(key)
+  }
 }
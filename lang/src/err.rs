@@ -1,17 +1,159 @@
-pub enum ParseError {
-  Msg(&'static str),
-  SubFile(&'static str,String,usize,usize)
+use crate::ast::{Node,Span};
+
+//what went wrong, tools can match on these and the codes never change
+#[derive(Debug,Clone,PartialEq)]
+pub enum ErrorKind {
+  //the syntax isn't right, the message says what we wanted instead
+  Unexpected(&'static str),
+  BadSymbol,
+  InputEnded,
+  OutOfRange(&'static str),
+  UnknownName{suggestions:Vec<String>},
+  //names has every function there was to pick from, defs included
+  UnknownFunction{suggestions:Vec<String>,names:Vec<String>},
+  UnknownScale{suggestions:Vec<String>},
+  //the right syntax but the wrong sort of value for the spot
+  WrongType(&'static str),
+  WrongArgs(&'static str),
+  GroupSize,
+  NoFile,
+  FileLoop,
+  //a keyword where a name should be, it's kept so the help can say which
+  Keyword(&'static str)
 }
 
-impl From<&'static str> for ParseError {
-  fn from(msg:&'static str) -> Self {
-    Self::Msg(msg)
+impl ErrorKind {
+  pub fn code(&self) -> &'static str {
+    match self {
+      Self::Unexpected(_) => "E001",
+      Self::BadSymbol => "E002",
+      Self::InputEnded => "E003",
+      Self::OutOfRange(_) => "E004",
+      Self::UnknownName{..} => "E005",
+      Self::UnknownFunction{..} => "E006",
+      Self::UnknownScale{..} => "E007",
+      Self::WrongType(_) => "E008",
+      Self::WrongArgs(_) => "E009",
+      Self::GroupSize => "E010",
+      Self::NoFile => "E011",
+      Self::FileLoop => "E012",
+      Self::Keyword(_) => "E013"
+    }
+  }
+
+  pub fn msg(&self) -> &'static str {
+    match self {
+      Self::Unexpected(msg) | Self::OutOfRange(msg) | Self::WrongType(msg) | Self::WrongArgs(msg) => msg,
+      Self::BadSymbol => "we couldn't figure this symbol out",
+      Self::InputEnded => "woah, the input ran out",
+      Self::UnknownName{..} => "we couldn't find a value with this name",
+      Self::UnknownFunction{..} => "we don't know a function with this name",
+      Self::UnknownScale{..} => "we don't know this scale",
+      Self::GroupSize => "the steps in this group don't add up to its size",
+      Self::NoFile => "couldn't open file",
+      Self::FileLoop => "this file is already being loaded",
+      Self::Keyword(_) => "keywords can't be used as names"
+    }
+  }
+
+  pub fn help(&self) -> Option<String> {
+    let fallback = match self {
+      Self::UnknownName{suggestions} | Self::UnknownFunction{suggestions,..} | Self::UnknownScale{suggestions}
+      if !suggestions.is_empty() => return Some(did_you_mean(suggestions)),
+      Self::UnknownFunction{names,..} => return Some(format!("the functions are {}",listed(names))),
+      Self::Keyword(kw) => return Some(format!("`{}` is a keyword, a name like `my_{}` works instead",kw,kw)),
+      Self::BadSymbol => "check for a typo, characters like '@' or '$' aren't part of the language",
+      Self::InputEnded => "something isn't finished, maybe there's a missing bracket",
+      Self::UnknownName{..} => "names need to be set with \"name = ...\" before they get used",
+      Self::UnknownScale{..} => "scales are things like major, minor, dorian or blues",
+      Self::GroupSize => "a group like (3: X X X) needs as many steps in it as the number at the start",
+      Self::NoFile => "paths start from the folder of the file they're in, or from wherever sgv was run",
+      Self::FileLoop => "a file can't do_file itself, not even through another file",
+      _ => return None
+    };
+
+    Some(fallback.to_string())
+  }
+}
+
+impl std::fmt::Display for ErrorKind {
+  fn fmt(&self, f:&mut std::fmt::Formatter) -> Result<(),std::fmt::Error> {
+    write!(f,"[{}] {}",self.code(),self.msg())
+  }
+}
+
+fn did_you_mean(names:&[String]) -> String {
+  let quoted : Vec<_> = names.iter().map(|n|format!("\"{}\"",n)).collect();
+  match quoted.as_slice() {
+    [one] => format!("did you mean {}?",one),
+    [rest@..,last] => format!("did you mean {} or {}?",rest.join(", "),last),
+    [] => unreachable!("only called with some names")
+  }
+}
+
+//"a, b and c"
+fn listed(names:&[String]) -> String {
+  match names {
+    [one] => one.clone(),
+    [rest@..,last] => format!("{} and {}",rest.join(", "),last),
+    [] => unreachable!("the builtins are always there")
+  }
+}
+
+//the closest few names to one that was misspelled, nearest first
+pub fn suggest<'a,I:Iterator<Item=&'a str>>(nm:&str,names:I) -> Vec<String> {
+  let most = (nm.chars().count() / 2).max(1);
+  let mut close : Vec<(usize,&str)> = names
+  .map(|cand|(distance(nm,cand),cand))
+  .filter(|(d,_)|*d <= most)
+  .collect();
+
+  close.sort();
+  close.dedup();
+  close.into_iter().take(3).map(|(_,cand)|cand.to_string()).collect()
+}
+
+//levenshtein, how many letters need adding, removing or swapping
+fn distance(a:&str,b:&str) -> usize {
+  let b : Vec<char> = b.chars().collect();
+  let mut row : Vec<usize> = (0..=b.len()).collect();
+
+  for (i,ca) in a.chars().enumerate() {
+    let mut diag = row[0];
+    row[0] = i + 1;
+    for (j,cb) in b.iter().enumerate() {
+      let above = row[j + 1];
+      row[j + 1] = if ca == *cb {
+        diag
+      }
+      else {
+        1 + diag.min(above).min(row[j])
+      };
+      diag = above;
+    }
+  }
+
+  row[b.len()]
+}
+
+//parsing only ever fails at the lexer's spot,
+//running the tree can fail anywhere so these carry their own spans
+#[derive(Debug)]
+pub enum EvalError {
+  At(ErrorKind,Span),
+  //path, text of the file, where in it and what went wrong
+  SubFile(String,String,Span,ErrorKind)
+}
+
+impl<T> Node<T> {
+  pub fn err(&self,kind:ErrorKind) -> EvalError {
+    EvalError::At(kind,self.span.clone())
   }
 }
 
 pub enum Error<'a> {
-  Root(&'a str,usize,usize,&'static str),
-  Sub(&'a str,String,usize,usize,&'static str)
+  Root(&'a str,usize,usize,ErrorKind),
+  Sub(String,String,usize,usize,ErrorKind)
 }
 
 impl Error<'_> {
@@ -31,13 +173,33 @@ impl Error<'_> {
     src[..s].lines().count()
   }
 
-  pub fn msg(&self) -> &'static str {
+  //byte range in the root source, or in the file for errors from a file
+  pub fn span(&self) -> Span {
+    match self {
+      Self::Root(_,s,e,_) => *s..*e,
+      Self::Sub(_,_,s,e,_) => *s..*e
+    }
+  }
+
+  pub fn kind(&self) -> &ErrorKind {
     match self {
-      Self::Root(_,_,_,msg) => msg,
-      Self::Sub(_,_,_,_,msg) => msg
+      Self::Root(_,_,_,kind) => kind,
+      Self::Sub(_,_,_,_,kind) => kind
     }
   }
 
+  pub fn code(&self) -> &'static str {
+    self.kind().code()
+  }
+
+  pub fn msg(&self) -> &'static str {
+    self.kind().msg()
+  }
+
+  pub fn help(&self) -> Option<String> {
+    self.kind().help()
+  }
+
   pub fn pre_txt(&self) -> &str {
     let (src,s) = match self {
       Self::Root(src,s,_,_) => (*src,*s),
@@ -65,3 +227,23 @@ impl Error<'_> {
     &src[e..ln_end]
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_suggest() {
+    let names = ["kick","kik","snare","hat","oontz"];
+    assert_eq!(suggest("kikc",names.into_iter()),["kik","kick"]);
+    assert_eq!(suggest("snar",names.into_iter()),["snare"]);
+    assert!(suggest("bass",names.into_iter()).is_empty());
+
+    let kind = ErrorKind::UnknownName{suggestions:suggest("oonts",names.into_iter())};
+    assert_eq!(kind.help().unwrap(),"did you mean \"oontz\"?");
+    assert_eq!(kind.code(),"E005");
+
+    let kind = ErrorKind::UnknownName{suggestions:vec!["a".into(),"b".into(),"c".into()]};
+    assert_eq!(kind.help().unwrap(),"did you mean \"a\", \"b\" or \"c\"?");
+  }
+}
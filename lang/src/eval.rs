@@ -0,0 +1,547 @@
+use super::{Env,Runtime,Slots,SLOT_COUNT,funcs,err::EvalError,env::Def};
+use crate::err::ErrorKind::{UnknownName,WrongType,GroupSize,OutOfRange};
+use crate::ast::{Node,Stmt,Expr,Op,Step,Steps,Assign,Target};
+use crate::seq_types::{Val,Trigger,LineBuilder};
+
+//walks the tree in order, a statement that goes wrong gets
+//skipped and the rest still run
+pub fn run<RT:Runtime>(stmts:&[Node<Stmt>],env:&mut Env<RT>) -> Result<(),Vec<EvalError>> {
+  let mut errs = vec![];
+  for s in stmts.iter() {
+    let res = match &s.item {
+      Stmt::DoFile(path) => env.do_file(path),
+      Stmt::For(nm,over,body) => for_loop(nm,over,body,env),
+      _ => stmt(s,env).map_err(|e|vec![e])
+    };
+
+    if let Err(es) = res {
+      errs.extend(es);
+    }
+  }
+
+  match errs.is_empty() {
+    true => Ok(()),
+    false => Err(errs)
+  }
+}
+
+fn stmt<RT:Runtime>(s:&Node<Stmt>,env:&mut Env<RT>) -> Result<(),EvalError> {
+  match &s.item {
+    Stmt::Assign(nm,v) => {
+      let value = expr(v,env)?;
+      env.set(&nm.item,value);
+    },
+    Stmt::SlotAssign(snum,v) => {
+      let (len,evs) = match expr(v,env)? {
+        Val::Bar(len,evs) => (len,evs),
+        _ => return Err(v.err(WrongType("you can only assign bars or sequences to a slot")))
+      };
+
+      //a name that isn't a slot or a number yet becomes a new slot name
+      let snum = match &snum.item {
+        Expr::Name(nm) => match (env.slot_num(nm),env.lookup(nm)) {
+          (Some(n),_) => n,
+          (None,Some(Val::Num(_))) => slot(snum,env)?,
+          (None,_) => env.new_slot(nm).ok_or_else(||snum.err(OutOfRange("there are no free slots left")))?
+        },
+        _ => slot(snum,env)?
+      };
+      env.assign_slot(snum,len,evs)
+    },
+    Stmt::SlotName(nm,snum) => {
+      let snum = slot(snum,env)?;
+      env.name_slot(&nm.item,snum)
+    },
+    Stmt::Bpm(bpm_num,None) => {
+      let bpm_num = tempo(bpm_num,env)?;
+      env.set_bpm(bpm_num)
+    },
+    Stmt::Bpm(bpm_num,Some(bars)) => {
+      let (bpm_num,bars) = (tempo(bpm_num,env)?,whole(bars,env)?);
+      env.ramp_bpm(bpm_num,bars)
+    },
+    Stmt::Tap => env.tap(),
+    Stmt::Div(which,div_num) => {
+      let (slots,div_num) = (slots(which,env)?,whole(div_num,env)?);
+      env.set_div(&slots,div_num)
+    },
+    Stmt::Sync(which,sync_num) => {
+      let (slots,sync_num) = (slots(which,env)?,whole(sync_num,env)?);
+      env.set_sync(&slots,sync_num)
+    },
+    Stmt::Play(which) => {
+      let slots = slots(which,env)?;
+      env.play_slots(&slots)
+    },
+    Stmt::Stop(which) => {
+      let slots = slots(which,env)?;
+      env.stop_slots(&slots)
+    },
+    Stmt::ListOuts => env.list_outs(),
+    Stmt::OpenOut(out_num,chan) => {
+      let (out_num,chan_num) = (whole(out_num,env)?,whole(chan,env)?);
+      if chan_num > 15 {
+        return Err(chan.err(OutOfRange("channel is out of range, must be 0 - 15")));
+      }
+      env.open_out(out_num,chan_num as u8)
+    },
+    Stmt::SetOut(which,out_num) => {
+      let (slots,out_num) = (slots(which,env)?,whole(out_num,env)?);
+      env.set_output(&slots,out_num)
+    },
+    Stmt::FindLps => env.list_lps(),
+    Stmt::OpenLp(lp_num) => {
+      let lp_num = whole(lp_num,env)?;
+      env.open_lp(lp_num)
+    },
+    Stmt::ClockOut(out_num,on) => {
+      let out_num = whole(out_num,env)?;
+      env.clock_out(out_num,on.item)
+    },
+    Stmt::ListIns => env.list_ins(),
+    Stmt::ClockStats => env.clock_stats(),
+    Stmt::Pause => env.pause(),
+    Stmt::Continue => env.resume(),
+    Stmt::Restart => env.restart(),
+    Stmt::Locate(bar) => {
+      let bar = whole(bar,env)?;
+      env.locate(bar)
+    },
+    Stmt::ClockIn(in_num) => {
+      let in_num = in_num.as_ref().map(|n|whole(n,env)).transpose()?;
+      env.clock_in(in_num)
+    },
+    Stmt::DoFile(_) | Stmt::For(..) => unreachable!("run does files and loops so it can keep all their errors"),
+    Stmt::Key(key) => env.set_key(key.item),
+    Stmt::Def(nm,params,body) => {
+      let params = params.iter().map(|p|p.item.clone()).collect();
+      env.define(&nm.item,Def{params,body:body.clone()});
+    }
+  };
+
+  Ok(())
+}
+
+//the name gets set like any other so it's still there after the loop.
+//once a time round goes wrong we stop, otherwise one mistake shows up 64 times
+fn for_loop<RT:Runtime>(nm:&Node<String>,over:&Node<Expr>,body:&[Node<Stmt>],env:&mut Env<RT>) -> Result<(),Vec<EvalError>> {
+  let items = match expr(over,env).map_err(|e|vec![e])? {
+    Val::List(items) => items,
+    _ => return Err(vec![over.err(WrongType("we can only loop over a range or a list"))])
+  };
+
+  for v in items {
+    env.set(&nm.item,v);
+    run(body,env)?;
+  }
+  Ok(())
+}
+
+pub fn expr<RT:Runtime>(e:&Node<Expr>,env:&mut Env<RT>) -> Result<Val,EvalError> {
+  match &e.item {
+    Expr::Trigger(t) => Ok(Val::Trigger(t.clone())),
+    Expr::Bar(steps) => {
+      let mut lb = LineBuilder::default();
+      bar(steps,env,&mut lb)?;
+      let (len,evs) = lb.done();
+      Ok(Val::Bar(len,evs))
+    },
+    Expr::Seq(assigns,bars) => {
+      //names in a sequence only live as long as the sequence
+      env.step_in();
+      let result = sequence(assigns,bars,env);
+      env.step_out();
+      result
+    },
+    Expr::Name(nm) => match (env.lookup(nm),env.slot_num(nm)) {
+      (Some(v),_) => Ok(v.clone()),
+      (None,Some(n)) => Ok(Val::Num(n as f64)),
+      (None,None) => Err(e.err(UnknownName{suggestions:env.similar(nm)}))
+    },
+    Expr::Call(nm,args) => funcs::call(nm,args,env),
+    Expr::Bin(op,a,b) => binary(e,*op,a,b,env),
+    Expr::Neg(v) => match expr(v,env)? {
+      Val::Num(n) => Ok(Val::Num(-n)),
+      _ => Err(v.err(WrongType("only numbers can have a - in front")))
+    },
+    //counts down too if the end is smaller
+    Expr::Range(a,b) => {
+      let (from,to) = (funcs::int(a,env)?,funcs::int(b,env)?);
+      let nums : Vec<isize> = match from <= to {
+        true => (from..=to).collect(),
+        false => (to..=from).rev().collect()
+      };
+      Ok(Val::List(nums.into_iter().map(|n|Val::Num(n as f64)).collect()))
+    },
+    Expr::Num(n) => Ok(Val::Num(*n)),
+    Expr::Str(txt) => Ok(Val::Str(txt.clone()))
+  }
+}
+
+//maths on numbers, a * on a bar or a trigger repeats it and + sticks strings or lists together
+fn binary<RT:Runtime>(e:&Node<Expr>,op:Op,a:&Node<Expr>,b:&Node<Expr>,env:&mut Env<RT>) -> Result<Val,EvalError> {
+  let (l,r) = (expr(a,env)?,expr(b,env)?);
+  match (op,l,r) {
+    (Op::Add,Val::Num(x),Val::Num(y)) => Ok(Val::Num(x + y)),
+    (Op::Sub,Val::Num(x),Val::Num(y)) => Ok(Val::Num(x - y)),
+    (Op::Mul,Val::Num(x),Val::Num(y)) => Ok(Val::Num(x * y)),
+    (Op::Div,Val::Num(_),Val::Num(0.0)) => Err(b.err(OutOfRange("you can't divide by zero"))),
+    (Op::Div,Val::Num(x),Val::Num(y)) => Ok(Val::Num(x / y)),
+    (Op::Mul,v,Val::Num(n)) => {
+      if n < 1.0 || n.fract() != 0.0 {
+        return Err(b.err(OutOfRange("you can only repeat something a whole number of times, 1 or more")));
+      }
+      v.repeat(n as usize).ok_or_else(||a.err(WrongType("only bars and triggers can be repeated")))
+    },
+    (Op::Add,Val::Str(x),Val::Str(y)) => Ok(Val::Str(x + &y)),
+    (Op::Add,Val::Str(x),Val::Num(y)) => Ok(Val::Str(format!("{}{}",x,y))),
+    (Op::Add,Val::List(mut x),Val::List(y)) => {
+      x.extend(y);
+      Ok(Val::List(x))
+    },
+    _ => Err(e.err(WrongType("these two don't go together like that")))
+  }
+}
+
+//numbers for keywords and slots have to come out whole, 0 or more
+pub fn whole<RT:Runtime>(e:&Node<Expr>,env:&mut Env<RT>) -> Result<usize,EvalError> {
+  let v = expr(e,env)?;
+  as_whole(e,&v)
+}
+
+fn as_whole(e:&Node<Expr>,v:&Val) -> Result<usize,EvalError> {
+  match v {
+    Val::Num(n) if *n >= 0.0 && n.fract() == 0.0 => Ok(*n as usize),
+    Val::Num(_) => Err(e.err(OutOfRange("this has to be a whole number, 0 or more"))),
+    _ => Err(e.err(WrongType("we need a number here")))
+  }
+}
+
+//a bpm can have a fraction, like 92.5, but it can't stand still
+fn tempo<RT:Runtime>(e:&Node<Expr>,env:&mut Env<RT>) -> Result<f64,EvalError> {
+  match expr(e,env)? {
+    Val::Num(n) if n > 0.0 => Ok(n),
+    Val::Num(_) => Err(e.err(OutOfRange("the bpm has to be more than 0"))),
+    _ => Err(e.err(WrongType("we need a number here")))
+  }
+}
+
+//every slot a keyword asked for, a range or a list counts as all the slots in it
+fn slots<RT:Runtime>(which:&Node<Target>,env:&mut Env<RT>) -> Result<Slots,EvalError> {
+  let Target::These(es) = &which.item else {
+    return Ok(Slots::All);
+  };
+
+  let mut nums = vec![];
+  for e in es.iter() {
+    //a slot name wins over any other name here
+    if let Expr::Name(nm) = &e.item && let Some(n) = env.slot_num(nm) {
+      nums.push(n);
+      continue;
+    }
+
+    match expr(e,env)? {
+      Val::List(vs) => for v in vs.iter() {
+        nums.push(as_slot(e,v)?);
+      },
+      v => nums.push(as_slot(e,&v)?)
+    }
+  }
+  Ok(Slots::These(nums))
+}
+
+fn slot<RT:Runtime>(e:&Node<Expr>,env:&mut Env<RT>) -> Result<usize,EvalError> {
+  let v = expr(e,env)?;
+  as_slot(e,&v)
+}
+
+fn as_slot(e:&Node<Expr>,v:&Val) -> Result<usize,EvalError> {
+  match as_whole(e,v)? {
+    n if n < SLOT_COUNT => Ok(n),
+    _ => Err(e.err(OutOfRange("there are only 64 slots, 0 to 63")))
+  }
+}
+
+fn sequence<RT>(assigns:&[Assign],bars:&[Node<Steps>],env:&mut Env<RT>) -> Result<Val,EvalError>
+where
+  RT:Runtime
+{
+  for (nm,v) in assigns.iter() {
+    let value = expr(v,env)?;
+    env.set(&nm.item,value);
+  }
+
+  let mut lb = LineBuilder::default();
+  for b in bars.iter() {
+    bar(&b.item,env,&mut lb)?;
+    lb.cr();
+  }
+
+  let (len,evs) = lb.done();
+  Ok(Val::Bar(len,evs))
+}
+
+fn bar<RT:Runtime>(steps:&[Node<Step>],env:&mut Env<RT>,line:&mut LineBuilder) -> Result<(),EvalError> {
+  for s in steps.iter() {
+    match &s.item {
+      Step::Rest(count) => line.rests(*count),
+      Step::Group{count,steps,reps} => {
+        let mut inner = LineBuilder::default();
+        bar(steps,env,&mut inner)?;
+        let (len,evs) = inner.done();
+
+        if len != *count || *count == 0 {
+          return Err(s.err(GroupSize));
+        }
+
+        for _ in 0..*reps {
+          line.squeeze(len,&evs);
+        }
+      },
+      Step::Hit{target,chance,ties,legato,reps} => match expr(target,env)? {
+        v@(Val::Trigger(_) | Val::Bar(..)) => hit(v,*chance,*ties,*legato,*reps,line),
+        _ => return Err(target.err(WrongType("only triggers and bars can go in a bar")))
+      }
+    }
+  }
+
+  Ok(())
+}
+
+//a chance on a step scales the chance the trigger or the bar's notes already had,
+//so X?50 with X = (C_1 100 ?40) plays 20% of the time
+fn scaled(prob:u8,chance:Option<u8>) -> u8 {
+  match chance {
+    Some(c) => ((prob as usize * c as usize)/100) as u8,
+    None => prob
+  }
+}
+
+fn hit(val:Val,chance:Option<u8>,ties:usize,legato:bool,reps:usize,line:&mut LineBuilder) {
+  let val = match val {
+    Val::Bar(len,mut evs) => {
+      evs.values_mut().flatten().for_each(|n|n.prob = scaled(n.prob,chance));
+      Val::Bar(len,evs)
+    },
+    v => v
+  };
+
+  for _ in 0..reps {
+    match &val {
+      Val::Trigger(Trigger{pitches,vel,prob}) => {
+        line.trig(pitches,*vel,ties,legato,scaled(*prob,chance))
+      },
+      Val::Bar(len,evs) => {
+        line.merge(*len,evs);
+        line.rests(ties);
+      },
+      _ => unreachable!("bar only lets triggers and bars in")
+    };
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::test_utils;
+  use crate::seq_types::Pitch;
+  use super::*;
+
+  #[test]
+  fn test_sequence_scope() {
+    let mut env = Env::new(crate::NullRuntime);
+    env.set("X",Val::Trigger(Trigger{pitches:vec![Pitch::Note(1)],vel:1,prob:100}));
+
+    let v = test_utils::eval_val(&mut env,"{ y = X \n [y -] \n [X - - -] }").expect("shouldn't error");
+    assert!(matches!(v,Val::Bar(4,_)));
+    assert!(env.lookup("y").is_none());
+
+    assert!(test_utils::eval_val(&mut env,"{ y = X \n [z] }").is_err());
+    assert!(env.lookup("y").is_none());
+  }
+
+  #[test]
+  fn test_suggestions() {
+    let mut env = Env::new(crate::NullRuntime);
+    env.set("kick",Val::Trigger(Trigger{pitches:vec![Pitch::Note(36)],vel:100,prob:100}));
+    env.step_in();
+    env.set("kik",Val::Trigger(Trigger{pitches:vec![Pitch::Note(36)],vel:100,prob:100}));
+
+    let e = test_utils::eval_val(&mut env,"[kikc -]").expect_err("should be unknown");
+    assert_eq!(e,UnknownName{suggestions:vec!["kik".into(),"kick".into()]});
+    assert_eq!(e.help().unwrap(),"did you mean \"kik\" or \"kick\"?");
+
+    let e = test_utils::eval_val(&mut env,"eucc(3,8,kick)").expect_err("should be unknown");
+    assert!(matches!(e,crate::ErrorKind::UnknownFunction{ref suggestions,..} if suggestions == &["euc"]));
+
+    let e = test_utils::eval_val(&mut env,"zzz").expect_err("should be unknown");
+    assert_eq!(e,UnknownName{suggestions:vec![]});
+    assert!(e.help().is_some());
+  }
+
+  #[test]
+  fn test_keep_going() {
+    let mut env = Env::new(crate::NullRuntime);
+    let src = "a = [nope]\nb = (C_1 100)\n<0> = b\nc = [b -]";
+    let mut lx = crate::Lexer::new(src);
+    let (tree,_) = crate::parse_fsm::root(&mut lx);
+
+    let errs = run(&tree,&mut env).expect_err("should have errors");
+    let spots : Vec<_> = errs.iter().map(|e|match e {
+      EvalError::At(_,span) => &src[span.clone()],
+      _ => panic!("there's no files in here")
+    }).collect();
+    assert_eq!(spots,["nope","b"]);
+    assert!(matches!(&errs[1],EvalError::At(WrongType(_),_)));
+    assert!(env.lookup("a").is_none());
+    assert!(env.lookup("c").is_some());
+  }
+
+  #[test]
+  fn test_values() {
+    let mut env = Env::new(crate::NullRuntime);
+    let src = "X = (C_1 100)\ntempo = 128\nbpm tempo / 2\nhalf = tempo / 2 - 4*2\nname = \"kick\" + 2\nl = list(1,name,[X])";
+    assert!(crate::parse(src,&mut env).is_ok());
+    assert!(matches!(env.lookup("half"),Some(Val::Num(56.0))));
+    assert!(matches!(env.lookup("name"),Some(Val::Str(s)) if s == "kick2"));
+
+    assert!(matches!(test_utils::eval_val(&mut env,"-3 + +1"),Ok(Val::Num(-2.0))));
+    assert!(matches!(test_utils::eval_val(&mut env,"at(l,1)"),Ok(Val::Str(_))));
+    assert!(matches!(test_utils::eval_val(&mut env,"at(l,3)"),Err(OutOfRange(_))));
+    assert!(matches!(test_utils::eval_val(&mut env,"X*0"),Err(OutOfRange(_))));
+    assert!(matches!(test_utils::eval_val(&mut env,"X + 1"),Err(WrongType(_))));
+    assert!(matches!(test_utils::eval_val(&mut env,"[X tempo]"),Err(WrongType(_))));
+    assert!(matches!(test_utils::eval_val(&mut env,"rot([X - -],tempo / 64)"),Ok(Val::Bar(3,_))));
+
+    env.set("n",Val::Num(2.0));
+    assert!(matches!(test_utils::eval_val(&mut env,"[X -]*n"),Ok(Val::Bar(4,_))));
+
+    let kinds = |src| match crate::parse(src,&mut Env::new(crate::NullRuntime)) {
+      Ok(()) => vec![],
+      Err(errs) => errs.iter().map(|e|e.kind().clone()).collect()
+    };
+    assert!(matches!(kinds("bpm 0 - 1")[..],[OutOfRange(_)]));
+    assert!(matches!(kinds("bpm 0\nbpm 120 over 1.5")[..],[OutOfRange(_),OutOfRange(_)]));
+    assert!(matches!(kinds("open_output 0 8 * 2")[..],[OutOfRange(_)]));
+    assert!(matches!(kinds("play \"a\"\nstop 1.5")[..],[WrongType(_),OutOfRange(_)]));
+    assert_eq!(kinds("n = 3\n<n + 1> = (C_1 100)*2\ndiv n 4"),vec![]);
+
+    let mut env = Env::new(test_utils::LogRt::default());
+    assert!(crate::parse("t = 140\nbpm 92.5\nbpm t over 16 / 2\ntap",&mut env).is_ok());
+    assert_eq!(env.runtime().0,["bpm 92.5","bpm 140 over 8","tap"]);
+
+    let mut env = Env::new(test_utils::LogRt::default());
+    assert!(crate::parse("pause\nlocate 16 / 2\ncontinue\nrestart",&mut env).is_ok());
+    assert_eq!(env.runtime().0,["pause","locate 8","continue","restart"]);
+    assert!(matches!(kinds("locate 0 - 1")[..],[OutOfRange(_)]));
+  }
+
+  #[test]
+  fn test_loops() {
+    let mut env = Env::new(test_utils::LogRt::default());
+    let src = "for i in 0..2 { div i 3 ; d = i * 2 }\nfor n in list(5,4) {\n  for j in 1..0 {\n    div n j\n  }\n}";
+    assert!(crate::parse(src,&mut env).is_ok());
+    assert_eq!(env.runtime().0,["div 0 3","div 1 3","div 2 3","div 5 1","div 5 0","div 4 1","div 4 0"]);
+    assert!(matches!(env.lookup("d"),Some(Val::Num(4.0))));
+
+    assert!(matches!(test_utils::eval_val(&mut env,"at(3..5,2)"),Ok(Val::Num(5.0))));
+    assert!(matches!(test_utils::eval_val(&mut env,"0..1.5"),Err(WrongType(_))));
+
+    //slots go to the runtime all together
+    let src = "kicks = list(8,9)\nplay 0..2\nstop 1,kicks,4..3\nsync ALL 96\nset_output 7, 0..1 2";
+    let mut env = Env::new(test_utils::LogRt::default());
+    assert!(crate::parse(src,&mut env).is_ok());
+    assert_eq!(env.runtime().0,["play 0,1,2","stop 1,8,9,4,3","sync all 96","set_output 7,0,1 2"]);
+    assert!(crate::parse("play 1,\"a\"\nstop 0..",&mut env).is_err());
+
+    //past the last slot is a mistake, not something to skip
+    let kinds = |src| match crate::parse(src,&mut Env::new(crate::NullRuntime)) {
+      Ok(()) => vec![],
+      Err(errs) => errs.iter().map(|e|e.kind().clone()).collect()
+    };
+    assert_eq!(kinds("play 63\nstop 0..63"),vec![]);
+    assert!(matches!(kinds("play 64\nstop 60..70\ndiv list(1,99) 2")[..],[OutOfRange(_),OutOfRange(_),OutOfRange(_)]));
+    assert!(matches!(kinds("<64> = [-]\nslot kick = 70")[..],[OutOfRange(_),OutOfRange(_)]));
+
+    //a mistake in the body only shows up once
+    let mut env = Env::new(crate::NullRuntime);
+    let Err(errs) = crate::parse("for i in 0..7 { div i nope }\nfor i in 3 { }",&mut env) else {
+      panic!("should have errors");
+    };
+    let kinds : Vec<_> = errs.iter().map(|e|e.kind().clone()).collect();
+    assert!(matches!(kinds[..],[UnknownName{..},WrongType(_)]));
+  }
+
+  #[test]
+  fn test_slot_names() {
+    //slots can have names, ones that aren't given a number get the first free one
+    let src = "kick = (C_1 100)\n<0> = kick*4\n<kick> = kick*2\nslot hats = 0\n<hats> = [kick]\nplay kick,hats\nstop hats + 1";
+    let mut env = Env::new(test_utils::LogRt::default());
+    assert!(crate::parse(src,&mut env).is_ok());
+    assert_eq!(env.runtime().0,["slot 0 4","slot kick = 1","slot 1 2","slot hats = 0","slot 0 1","play 1,0","stop 1"]);
+    assert!(matches!(crate::parse("play kik",&mut env),Err(e) if e[0].help().is_some_and(|h|h.contains("kick"))));
+  }
+
+  #[test]
+  fn test_no_free_slots() {
+    let mut src = String::from("X = (C_1 100)\n");
+    for n in 0..64 {
+      src += &format!("<s{}> = [X]\n",n);
+    }
+    let mut env = Env::new(crate::NullRuntime);
+    assert!(crate::parse(&src,&mut env).is_ok());
+    assert_eq!(env.slot_num("s63"),Some(63));
+
+    let Err(errs) = crate::parse("<one_more> = [X]",&mut env) else {
+      panic!("should have run out of slots");
+    };
+    assert!(matches!(errs[..],[ref e] if matches!(e.kind(),OutOfRange(_)) && e.txt() == "one_more"));
+    assert_eq!(env.slot_num("one_more"),None);
+  }
+
+  #[test]
+  fn test_file_errors() {
+    let path = std::env::temp_dir().join("sgv_test_file_errors.sgv");
+    std::fs::write(&path,"X = (C_1 100)\nplay\n<0> = X\n").expect("couldn't write the test file");
+
+    let mut env = Env::new(crate::NullRuntime);
+    let path_txt = path.to_string_lossy().to_string();
+    let errs = env.do_file(&Node::new(path_txt.clone(),0..0)).expect_err("should have errors");
+    assert_eq!(errs.len(),1);
+    assert!(matches!(&errs[0],EvalError::SubFile(p,_,span,_) if *p == path_txt && *span == (18..19)));
+
+    //it didn't parse so nothing ran
+    assert!(env.lookup("X").is_none());
+
+    std::fs::write(&path,"X = (C_1 100)\n<0> = X\n<1> = X\n").expect("couldn't write the test file");
+    let errs = env.do_file(&Node::new(path_txt,0..0)).expect_err("should have errors");
+    assert_eq!(errs.len(),2);
+    assert!(env.lookup("X").is_some());
+
+    let _ = std::fs::remove_file(&path);
+  }
+
+  #[test]
+  fn test_file_loops() {
+    //paths in a file start from that file, so these find each other from anywhere
+    let dir = std::env::temp_dir().join("sgv_test_file_loops");
+    std::fs::create_dir_all(dir.join("parts")).expect("couldn't make the test folder");
+    std::fs::write(dir.join("top.sgv"),"do_file parts/a.sgv\n").expect("couldn't write the test file");
+    std::fs::write(dir.join("parts/a.sgv"),"X = (C_1 100)\ndo_file b.sgv\n").expect("couldn't write the test file");
+    std::fs::write(dir.join("parts/b.sgv"),"do_file a.sgv\n").expect("couldn't write the test file");
+
+    let mut env = Env::new(crate::NullRuntime);
+    env.set_dir(&dir);
+
+    //the loop gets caught where it closes, in b
+    let errs = env.do_file(&Node::new("top.sgv".to_string(),0..0)).expect_err("should have errors");
+    assert!(matches!(&errs[..],[EvalError::SubFile(p,_,_,crate::ErrorKind::FileLoop)] if p == "b.sgv"));
+    assert!(env.lookup("X").is_some());
+
+    //once in a file paths don't start from the env's folder
+    std::fs::write(dir.join("parts/b.sgv"),"do_file top.sgv\n").expect("couldn't write the test file");
+    let errs = env.do_file(&Node::new("top.sgv".to_string(),0..0)).expect_err("should have errors");
+    assert!(matches!(&errs[..],[EvalError::SubFile(p,_,_,crate::ErrorKind::NoFile)] if p == "b.sgv"));
+
+    let _ = std::fs::remove_dir_all(&dir);
+  }
+}
@@ -0,0 +1,347 @@
+use super::{Lexer,Token};
+
+//what a bracket is being used for, the spacing depends on it
+#[derive(Copy,Clone,PartialEq)]
+enum Ctx {
+  Bar,
+  Group,
+  Trigger,
+  Call,
+  Seq,
+  Slot
+}
+
+//a bit of output text, depth is how many brackets are open around it
+//(brackets count as outside of themselves)
+struct Piece {
+  txt:String,
+  gap:bool,
+  depth:usize,
+  code:bool
+}
+
+#[derive(Default)]
+struct Line {
+  indent:usize,
+  pieces:Vec<Piece>
+}
+
+impl Line {
+  fn render(pieces:&[Piece]) -> String {
+    let mut out = String::new();
+    for p in pieces.iter() {
+      if p.gap && !out.is_empty() {
+        out.push(' ');
+      }
+      out.push_str(&p.txt);
+    }
+    out
+  }
+
+  fn text(&self) -> String {
+    format!("{}{}","  ".repeat(self.indent),Line::render(&self.pieces))
+  }
+
+  //the bit before the bar, the steps in it and the bit after,
+  //only if there's one bar that's all on this line with no comments in it
+  fn bar(&self) -> Option<(String,Vec<String>,String)> {
+    let open = self.pieces.iter().position(|p|p.code && p.txt == "[")?;
+    let depth = self.pieces[open].depth;
+    let close = open + self.pieces[open..].iter().position(|p|p.code && p.txt == "]" && p.depth == depth)?;
+
+    let inside = &self.pieces[open + 1..close];
+    if inside.iter().any(|p|!p.code) || self.pieces.iter().any(|p|p.txt == "\\") {
+      return None;
+    }
+
+    let mut steps : Vec<&[Piece]> = vec![];
+    let mut start = 0;
+    for (i,p) in inside.iter().enumerate() {
+      if i > 0 && p.gap && p.depth == depth + 1 {
+        steps.push(&inside[start..i]);
+        start = i;
+      }
+    }
+    if !inside.is_empty() {
+      steps.push(&inside[start..]);
+    }
+
+    let pre = format!("{}{}","  ".repeat(self.indent),Line::render(&self.pieces[..=open]));
+    let post = Line::render(&self.pieces[close..]);
+    Some((pre,steps.into_iter().map(Line::render).collect(),post))
+  }
+}
+
+struct Formatter<'a> {
+  src:&'a str,
+  lines:Vec<Line>,
+  line:Line,
+  stack:Vec<Ctx>,
+  prev:Token,
+  before:Token,
+  prev_end:usize,
+  glue:bool,
+  continued:bool,
+  after_key:bool
+}
+
+//rewrites a set file in the one true layout, it has to parse first
+//so we never mess up something we don't understand
+pub fn format(src:&str) -> Result<String,Vec<crate::Error<'_>>> {
+  crate::parse_tree(src)?;
+
+  let mut f = Formatter{
+    src,
+    lines:vec![],
+    line:Line::default(),
+    stack:vec![],
+    prev:Token::Eol,
+    before:Token::Eol,
+    prev_end:0,
+    glue:false,
+    continued:false,
+    after_key:false
+  };
+
+  let mut lx = Lexer::new(src);
+  loop {
+    let t = lx.next();
+    let span = if t == Token::Eof { src.len()..src.len() } else { lx.span() };
+    f.gap(f.prev_end..span.start);
+
+    match t {
+      Token::Eof => break,
+      Token::Eol => f.newline(),
+      _ => f.token(t,lx.slice().to_string(),span.start)
+    }
+
+    f.prev_end = span.end;
+  }
+
+  f.newline();
+  Ok(f.finish())
+}
+
+impl Formatter<'_> {
+  fn top(&self) -> Option<Ctx> {
+    self.stack.last().copied()
+  }
+
+  fn in_bar(&self) -> bool {
+    matches!(self.top(),Some(Ctx::Bar | Ctx::Group))
+  }
+
+  fn push(&mut self,txt:String,gap:bool,code:bool) {
+    if self.line.pieces.is_empty() {
+      self.line.indent = self.stack.len() + self.continued as usize;
+    }
+
+    let depth = self.stack.len();
+    self.line.pieces.push(Piece{txt,gap,depth,code});
+  }
+
+  fn token(&mut self,t:Token,txt:String,start:usize) {
+    let adjacent = self.prev_end == start && self.prev != Token::Eol;
+    let txt = match t {
+      t if t.is_keyword() => txt.to_lowercase(),
+      Token::Note | Token::Chord => upper_first(&txt),
+      Token::Sym if self.after_key => upper_first(&txt),
+      _ => txt
+    };
+    self.after_key = t == Token::Key;
+
+    let tight = match t {
+      Token::CloseParen | Token::CloseSq | Token::CloseAng | Token::Comma | Token::Pipe |
+      Token::Star | Token::Quest | Token::Colon | Token::DotDot | Token::Semi => true,
+      Token::Plus => self.top() == Some(Ctx::Trigger),
+      Token::Eq => self.in_bar(),
+      Token::OpenParen => {
+        (adjacent && matches!(self.prev,Token::Sym | Token::Digits))
+        || (self.before == Token::Def && self.prev == Token::Sym)
+      },
+      //"^5+^7" is two notes stacked, it has to stay together
+      _ => adjacent && self.top() == Some(Ctx::Trigger) && self.prev == Token::Degree
+    };
+    let gap = !(tight || self.glue);
+
+    //a sign sticks to the number, it's a sign when there's nothing to add or take away from
+    let sign = matches!(t,Token::Plus | Token::Dash)
+      && !self.in_bar() && self.top() != Some(Ctx::Trigger)
+      && (self.prev.is_keyword() || matches!(self.prev,
+        Token::Eq | Token::OpenParen | Token::Comma | Token::OpenAng |
+        Token::Plus | Token::Dash | Token::Star | Token::Slash | Token::DotDot
+      ));
+
+    match t {
+      Token::OpenParen | Token::OpenSq | Token::OpenCrl | Token::OpenAng => {
+        let ctx = match t {
+          Token::OpenSq => Ctx::Bar,
+          Token::OpenCrl => Ctx::Seq,
+          Token::OpenAng => Ctx::Slot,
+          _ if tight => Ctx::Call,
+          _ if self.in_bar() => Ctx::Group,
+          _ => Ctx::Trigger
+        };
+        self.push(txt,gap,true);
+        self.stack.push(ctx);
+      },
+      Token::CloseAng if self.top() != Some(Ctx::Slot) => self.push(txt,gap,true),
+      Token::CloseParen | Token::CloseSq | Token::CloseCrl | Token::CloseAng => {
+        self.stack.pop();
+        self.push(txt,gap,true);
+      },
+      _ => self.push(txt,gap,true)
+    }
+
+    self.glue = sign || matches!(t,
+      Token::OpenParen | Token::OpenSq | Token::OpenAng | Token::Comma | Token::Pipe | Token::Star | Token::Quest |
+      Token::DotDot
+    ) || (t == Token::Plus && self.top() == Some(Ctx::Trigger));
+    self.before = self.prev;
+    self.prev = t;
+  }
+
+  //whatever the lexer skipped between two tokens, that's comments and \ line endings
+  fn gap(&mut self,span:std::ops::Range<usize>) {
+    let txt = &self.src[span];
+    let mut i = 0;
+    while i < txt.len() {
+      let rest = &txt[i..];
+      if rest.starts_with('#') || rest.starts_with("//") {
+        let len = rest.find('\n').unwrap_or(rest.len());
+        self.push(rest[..len].trim_end().to_string(),true,false);
+        i += len;
+      }
+      else if let Some(body) = rest.strip_prefix("/*") {
+        let len = body.find("*/").map(|e|e + 4).unwrap_or(rest.len());
+        self.push(rest[..len].to_string(),true,false);
+        i += len;
+      }
+      else if rest.starts_with('\\') {
+        self.push("\\".to_string(),true,false);
+        self.end_line();
+        self.continued = true;
+        i += rest.find('\n').map(|e|e + 1).unwrap_or(rest.len());
+      }
+      else {
+        i += rest.chars().next().map(|c|c.len_utf8()).unwrap_or(1);
+      }
+    }
+  }
+
+  fn end_line(&mut self) {
+    let line = std::mem::take(&mut self.line);
+    self.lines.push(line);
+    self.prev = Token::Eol;
+    self.glue = false;
+  }
+
+  fn newline(&mut self) {
+    self.continued = false;
+
+    //no blank lines at the start and never more than one in a row
+    let blank = self.line.pieces.is_empty();
+    let last_blank = self.lines.last().is_none_or(|l|l.pieces.is_empty());
+    if blank && last_blank {
+      return;
+    }
+    self.end_line();
+  }
+
+  fn finish(mut self) -> String {
+    while self.lines.last().is_some_and(|l|l.pieces.is_empty()) {
+      self.lines.pop();
+    }
+
+    let mut out = vec![];
+    let mut group : Vec<(String,Vec<String>,String)> = vec![];
+    for line in self.lines.iter() {
+      match line.bar() {
+        Some(b) if group.first().is_none_or(|g|g.0.chars().count() == b.0.chars().count()) => group.push(b),
+        b => {
+          out.extend(align(std::mem::take(&mut group)));
+          match b {
+            Some(b) => group.push(b),
+            None => out.push(line.text())
+          }
+        }
+      }
+    }
+    out.extend(align(group));
+
+    let mut txt = out.join("\n");
+    txt.push('\n');
+    txt
+  }
+}
+
+//lines up the steps of bars on lines next to each other
+fn align(group:Vec<(String,Vec<String>,String)>) -> Vec<String> {
+  let mut widths : Vec<usize> = vec![];
+  for (_,steps,_) in group.iter() {
+    for (i,s) in steps.iter().enumerate() {
+      let w = s.chars().count();
+      match widths.get_mut(i) {
+        Some(x) => *x = w.max(*x),
+        None => widths.push(w)
+      }
+    }
+  }
+
+  group.into_iter().map(|(pre,steps,post)|{
+    let last = steps.len().saturating_sub(1);
+    let steps : Vec<_> = steps.iter().enumerate().map(|(i,s)|match i == last {
+      true => s.clone(),
+      false => format!("{:w$}",s,w = widths[i])
+    }).collect();
+    format!("{}{}{}",pre,steps.join(" "),post)
+  }).collect()
+}
+
+fn upper_first(txt:&str) -> String {
+  let mut cs = txt.chars();
+  match cs.next() {
+    Some(c) => c.to_uppercase().chain(cs).collect(),
+    None => String::new()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn fmt(src:&str) -> String {
+    let Ok(out) = format(src) else { panic!("should parse") };
+    let Ok(again) = format(&out) else { panic!("should still parse") };
+    assert_eq!(again,out,"formatting twice changed it");
+    out
+  }
+
+  #[test]
+  fn test_spacing() {
+    assert_eq!(fmt("X=( c_1   127 )"),"X = (C_1 127)\n");
+    assert_eq!(fmt("<  0 >=[X-C -  ]*2\nPLAY 0"),"<0> = [X - C -]*2\nplay 0\n");
+    assert_eq!(fmt("a = (c_3 + e_3+G_3 100 ? 50)"),"a = (C_3+E_3+G_3 100?50)\n");
+    assert_eq!(fmt("a = (^1+^3 100)\nb = [rot( a , -3 ) a?40==>*2]"),"a = (^1+^3 100)\nb = [rot(a,-3) a?40==>*2]\n");
+    assert_eq!(fmt("b = [X (3 : X X X) -]\nkey d   dorian"),"b = [X (3: X X X) -]\nkey D dorian\n");
+    assert_eq!(fmt("do_file   ./some drums.sgv"),"do_file ./some drums.sgv\n");
+    assert_eq!(fmt("DEF fill ( a , b )=[a a b -]"),"def fill(a,b) = [a a b -]\n");
+    assert_eq!(fmt("a = - 3\nbpm tempo/ 2+1\nb = [rot(a , n+ -1)]"),"a = -3\nbpm tempo / 2 + 1\nb = [rot(a,n + -1)]\n");
+    assert_eq!(fmt("FOR i IN -1 .. 7{div i 3 ;play i}"),"for i in -1..7 { div i 3; play i }\n");
+    assert_eq!(fmt("<n+1>=X *2\nc = 2 + 3 * 4\nd = \"kick\"+ \"s\""),"<n + 1> = X*2\nc = 2 + 3*4\nd = \"kick\" + \"s\"\n");
+  }
+
+  #[test]
+  fn test_layout() {
+    let src = "\n\n# drums\nX = (C_1 127)   // kick\n\n\n\ns = {\ny = X\n[X - C -]\n   [kick - - snare] /* hi */\n}\n\n";
+    let want = "# drums\nX = (C_1 127) // kick\n\ns = {\n  y = X\n  [X    - C -]\n  [kick - - snare] /* hi */\n}\n";
+    assert_eq!(fmt(src),want);
+
+    let src = "<0> = [X - C -] \\\n # next\n<1> = [(3: X X X) - X X]";
+    assert_eq!(fmt(src),"<0> = [X - C -] \\\n  # next\n<1> = [(3: X X X) - X X]\n");
+
+    assert_eq!(fmt("for i in 0..7 {\nfor j in list(a,b) {\ndiv j i\n}\n}"),"for i in 0..7 {\n  for j in list(a,b) {\n    div j i\n  }\n}\n");
+
+    assert!(format("a = [X").is_err());
+  }
+}
@@ -0,0 +1,348 @@
+use std::collections::HashMap;
+
+use super::{Env,Runtime,eval,err::{self,EvalError},env::Def};
+use crate::err::ErrorKind::{UnknownFunction,WrongArgs,WrongType,OutOfRange};
+use crate::ast::{Node,Expr};
+use crate::seq_types::{Val,Trigger,SeqNote,LineBuilder};
+
+pub const NAMES : &[&str] = &["euc","rev","rot","trans","vel","list","at"];
+
+//a def calling itself would go forever, this is plenty deep for anything else
+const MAX_DEPTH : usize = 64;
+
+//the args have been parsed but not run yet, each builtin
+//runs the ones it needs so it can point at the bad one
+pub fn call<RT:Runtime>(nm:&Node<String>,args:&[Node<Expr>],env:&mut Env<RT>) -> Result<Val,EvalError> {
+  match nm.item.as_str() {
+    "euc" => euc(nm,args,env),
+    "rev" => rev(nm,args,env),
+    "rot" => rot(nm,args,env),
+    "trans" => trans(nm,args,env),
+    "vel" => vel(nm,args,env),
+    "list" => list(args,env),
+    "at" => at(nm,args,env),
+    _ => match env.def(&nm.item) {
+      Some(def) => user(nm,&def,args,env),
+      None => {
+        let mut defs : Vec<&str> = env.def_names().collect();
+        defs.sort();
+        let names : Vec<String> = NAMES.iter().copied().chain(defs).map(String::from).collect();
+        let suggestions = err::suggest(&nm.item,names.iter().map(String::as_str));
+        Err(nm.err(UnknownFunction{suggestions,names}))
+      }
+    }
+  }
+}
+
+//a def from the set, the args get run where it's called and
+//then set as names in a fresh scope while the body runs
+fn user<RT:Runtime>(nm:&Node<String>,def:&Def,args:&[Node<Expr>],env:&mut Env<RT>) -> Result<Val,EvalError> {
+  if args.len() != def.params.len() {
+    return Err(nm.err(WrongArgs("this def takes a different number of arguments")));
+  }
+
+  if env.depth() > MAX_DEPTH {
+    return Err(nm.err(OutOfRange("defs went too deep, is one calling itself?")));
+  }
+
+  let vals = args.iter().map(|a|eval::expr(a,env)).collect::<Result<Vec<_>,_>>()?;
+
+  env.step_in();
+  for (p,v) in def.params.iter().zip(vals) {
+    env.set(p,v);
+  }
+  let result = eval::expr(&def.body,env);
+  env.step_out();
+  result
+}
+
+//euc(hits,steps,trigger) or euc(hits,steps,trigger,rotation)
+fn euc<RT:Runtime>(nm:&Node<String>,args:&[Node<Expr>],env:&mut Env<RT>) -> Result<Val,EvalError> {
+  let (hits,steps,trig,rot) = match args {
+    [hits,steps,trig] => (num(hits,env)?,num(steps,env)?,trig_arg(trig,env)?,0),
+    [hits,steps,trig,rot] => (num(hits,env)?,num(steps,env)?,trig_arg(trig,env)?,num(rot,env)?),
+    _ => return Err(nm.err(WrongArgs("euc needs hits, steps, a trigger and maybe a rotation")))
+  };
+
+  if steps == 0 {
+    return Err(args[1].err(OutOfRange("euc needs at least one step")));
+  }
+
+  if hits > steps {
+    return Err(args[0].err(OutOfRange("euc can't fit more hits than steps")));
+  }
+
+  let mut lb = LineBuilder::default();
+  for i in 0..steps {
+    //bresenham style, step n is a hit when the running total wraps
+    let n = (i + rot) % steps;
+    if (n * hits) % steps < hits {
+      lb.trig(&trig.pitches,trig.vel,0,false,trig.prob);
+    }
+    else {
+      lb.rests(1);
+    }
+  }
+
+  let (len,evs) = lb.done();
+  Ok(Val::Bar(len,evs))
+}
+
+//rev(bar) plays the bar backwards, tied notes keep their length
+fn rev<RT:Runtime>(nm:&Node<String>,args:&[Node<Expr>],env:&mut Env<RT>) -> Result<Val,EvalError> {
+  let [b] = args else {
+    return Err(nm.err(WrongArgs("rev needs just a bar")));
+  };
+  let (len,evs) = bar_arg(b,env)?;
+
+  let mut out : HashMap<usize,Vec<SeqNote>> = HashMap::new();
+  for (step,notes) in evs.into_iter() {
+    for mut n in notes.into_iter() {
+      //work in the note's own fraction of a step so groups come out right
+      let (pos,parts) = n.sub;
+      let start = (len * parts).saturating_sub(step * parts + pos + n.duration);
+      n.sub = (start % parts,parts);
+      out.entry(start / parts).or_default().push(n);
+    }
+  }
+
+  Ok(Val::Bar(len,out))
+}
+
+//rot(bar,n) moves every step n steps earlier, wrapping around the end
+fn rot<RT:Runtime>(nm:&Node<String>,args:&[Node<Expr>],env:&mut Env<RT>) -> Result<Val,EvalError> {
+  let [b,by] = args else {
+    return Err(nm.err(WrongArgs("rot needs a bar and how many steps to move it")));
+  };
+  let (len,evs) = bar_arg(b,env)?;
+  let by = int(by,env)?;
+
+  if len == 0 {
+    return Ok(Val::Bar(len,evs));
+  }
+
+  let shift = by.rem_euclid(len as isize) as usize;
+  let mut lb = LineBuilder::default();
+  lb.merge(len - shift,&shifted(&evs,shift));
+  lb.merge(shift,&evs);
+  let (len,evs) = lb.done();
+  Ok(Val::Bar(len,evs))
+}
+
+//trans(val,+7) shifts every note by some semitones
+fn trans<RT:Runtime>(nm:&Node<String>,args:&[Node<Expr>],env:&mut Env<RT>) -> Result<Val,EvalError> {
+  let [v,by] = args else {
+    return Err(nm.err(WrongArgs("trans needs a value and how many semitones to move it")));
+  };
+  let by_n = int(by,env)?;
+
+  let oob = "transposing pushes a note out of the 0-127 range";
+  match eval::expr(v,env)? {
+    Val::Trigger(mut t) => {
+      for p in t.pitches.iter_mut() {
+        *p = p.transpose(by_n).ok_or_else(||by.err(OutOfRange(oob)))?;
+      }
+      Ok(Val::Trigger(t))
+    },
+    Val::Bar(len,mut evs) => {
+      for n in evs.values_mut().flatten() {
+        n.pitch = n.pitch.transpose(by_n).ok_or_else(||by.err(OutOfRange(oob)))?;
+      }
+      Ok(Val::Bar(len,evs))
+    },
+    _ => Err(v.err(WrongType("trans needs a trigger or a bar")))
+  }
+}
+
+//vel(val,0.8) scales every velocity, it never goes past 127
+fn vel<RT:Runtime>(nm:&Node<String>,args:&[Node<Expr>],env:&mut Env<RT>) -> Result<Val,EvalError> {
+  let [v,scale] = args else {
+    return Err(nm.err(WrongArgs("vel needs a value and a number to scale it by")));
+  };
+  let scale = decimal(scale,env)? as f32;
+
+  let scaled = |vel:u8| (vel as f32 * scale).round().min(127.0) as u8;
+  match eval::expr(v,env)? {
+    Val::Trigger(mut t) => {
+      t.vel = scaled(t.vel);
+      Ok(Val::Trigger(t))
+    },
+    Val::Bar(len,mut evs) => {
+      evs.values_mut().flatten().for_each(|n|n.vel = scaled(n.vel));
+      Ok(Val::Bar(len,evs))
+    },
+    _ => Err(v.err(WrongType("vel needs a trigger or a bar")))
+  }
+}
+
+//list(a,b,c) keeps any values together, numbers and strings too
+fn list<RT:Runtime>(args:&[Node<Expr>],env:&mut Env<RT>) -> Result<Val,EvalError> {
+  let vals = args.iter().map(|a|eval::expr(a,env)).collect::<Result<Vec<_>,_>>()?;
+  Ok(Val::List(vals))
+}
+
+//at(list,i) is the thing at spot i, counting from 0
+fn at<RT:Runtime>(nm:&Node<String>,args:&[Node<Expr>],env:&mut Env<RT>) -> Result<Val,EvalError> {
+  let [l,i] = args else {
+    return Err(nm.err(WrongArgs("at needs a list and a spot in it")));
+  };
+
+  let Val::List(mut vals) = eval::expr(l,env)? else {
+    return Err(l.err(WrongType("at needs a list, like list(1,2,3)")));
+  };
+
+  let idx = num(i,env)?;
+  if idx >= vals.len() {
+    return Err(i.err(OutOfRange("the list isn't that long")));
+  }
+  Ok(vals.swap_remove(idx))
+}
+
+//the notes from step "from" on, moved to the start
+fn shifted(evs:&HashMap<usize,Vec<SeqNote>>,from:usize) -> HashMap<usize,Vec<SeqNote>> {
+  evs.iter()
+  .filter(|(step,_)|**step >= from)
+  .map(|(step,notes)|(step - from,notes.clone()))
+  .collect()
+}
+
+fn bar_arg<RT:Runtime>(arg:&Node<Expr>,env:&mut Env<RT>) -> Result<(usize,HashMap<usize,Vec<SeqNote>>),EvalError> {
+  match eval::expr(arg,env)? {
+    Val::Bar(len,evs) => Ok((len,evs)),
+    _ => Err(arg.err(WrongType("this function needs a bar or a sequence")))
+  }
+}
+
+fn trig_arg<RT:Runtime>(arg:&Node<Expr>,env:&mut Env<RT>) -> Result<Trigger,EvalError> {
+  match eval::expr(arg,env)? {
+    Val::Trigger(t) => Ok(t),
+    _ => Err(arg.err(WrongType("we need a trigger here")))
+  }
+}
+
+//a whole number, it can go below 0
+pub fn int<RT:Runtime>(arg:&Node<Expr>,env:&mut Env<RT>) -> Result<isize,EvalError> {
+  match eval::expr(arg,env)? {
+    Val::Num(n) if n.fract() == 0.0 => Ok(n as isize),
+    _ => Err(arg.err(WrongType("we need a whole number here, like 3 or -2")))
+  }
+}
+
+fn decimal<RT:Runtime>(arg:&Node<Expr>,env:&mut Env<RT>) -> Result<f64,EvalError> {
+  match eval::expr(arg,env)? {
+    Val::Num(n) => Ok(n),
+    _ => Err(arg.err(WrongType("we need a number here, like 2 or 0.5")))
+  }
+}
+
+fn num<RT:Runtime>(arg:&Node<Expr>,env:&mut Env<RT>) -> Result<usize,EvalError> {
+  eval::whole(arg,env)
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::test_utils::eval_val;
+  use crate::seq_types::Pitch;
+  use super::*;
+
+  fn steps(v:&Val) -> Vec<bool> {
+    match v {
+      Val::Bar(len,evs) => (0..*len).map(|i|evs.contains_key(&i)).collect(),
+      _ => panic!("should be a bar")
+    }
+  }
+
+  #[test]
+  fn test_defs() {
+    let mut env = Env::new(crate::NullRuntime);
+    let src = "X = (C_1 100)\nC = (D_1 100)\ndef fill(a,b) = [a a b -]\ndef forever(a) = forever(a)";
+    assert!(crate::parse(src,&mut env).is_ok());
+    assert!(crate::parse_tree("def rev(a) = a").is_err());
+
+    let v = eval_val(&mut env,"fill(X,C)").expect("shouldn't error");
+    assert_eq!(steps(&v),[true,true,true,false]);
+    assert!(env.lookup("a").is_none());
+
+    let v = eval_val(&mut env,"[fill(C,X) X]").expect("shouldn't error");
+    assert_eq!(steps(&v),[true,true,true,false,true]);
+
+    assert!(matches!(eval_val(&mut env,"fill(X)"),Err(WrongArgs(_))));
+    assert!(matches!(eval_val(&mut env,"forever(X)"),Err(OutOfRange(_))));
+    assert!(matches!(eval_val(&mut env,"fil(X,C)"),Err(UnknownFunction{suggestions,..}) if suggestions == ["fill"]));
+
+    //with nothing close the help lists everything, defs too
+    let e = eval_val(&mut env,"zzzzzz(X)").expect_err("should be unknown");
+    assert_eq!(e.help().unwrap(),"the functions are euc, rev, rot, trans, vel, list, at, fill and forever");
+  }
+
+  #[test]
+  fn test_euc() {
+    let mut env = Env::new(crate::NullRuntime);
+    env.set("X",Val::Trigger(Trigger{pitches:vec![Pitch::Note(1)],vel:1,prob:100}));
+
+    let v = eval_val(&mut env,"euc(3,8,X)").expect("shouldn't error");
+    assert_eq!(steps(&v),[true,false,false,true,false,false,true,false]);
+
+    let v = eval_val(&mut env,"euc(3,8,X,1)").expect("shouldn't error");
+    assert_eq!(steps(&v),[false,false,true,false,false,true,false,true]);
+
+    let v = eval_val(&mut env,"euc(4,4,(C_1 100))").expect("shouldn't error");
+    assert_eq!(steps(&v),[true,true,true,true]);
+
+    assert!(eval_val(&mut env,"euc(5,4,X)").is_err());
+
+    assert!(eval_val(&mut env,"euc(1,0,X)").is_err());
+
+    assert!(eval_val(&mut env,"cue(1,4,X)").is_err());
+  }
+
+  fn first_note(v:&Val) -> (usize,SeqNote) {
+    match v {
+      Val::Bar(len,evs) => {
+        let step = (0..*len).find(|i|evs.contains_key(i)).expect("should have a note");
+        (step,evs[&step][0].clone())
+      },
+      _ => panic!("should be a bar")
+    }
+  }
+
+  #[test]
+  fn test_transforms() {
+    let mut env = Env::new(crate::NullRuntime);
+    env.set("X",Val::Trigger(Trigger{pitches:vec![Pitch::Note(60)],vel:100,prob:100}));
+
+    let v = eval_val(&mut env,"rot(euc(3,8,X),3)").expect("shouldn't error");
+    assert_eq!(steps(&v),[true,false,false,true,false,true,false,false]);
+
+    let v = eval_val(&mut env,"rot(euc(3,8,X),-1)").expect("shouldn't error");
+    assert_eq!(steps(&v),[false,true,false,false,true,false,false,true]);
+
+    let v = eval_val(&mut env,"rev(euc(3,8,X))").expect("shouldn't error");
+    assert_eq!(steps(&v),[false,true,false,false,true,false,false,true]);
+
+    let v = eval_val(&mut env,"rev([X== -])").expect("shouldn't error");
+    let (step,n) = first_note(&v);
+    assert_eq!(step,1);
+    assert_eq!(n.duration,3);
+
+    let v = eval_val(&mut env,"trans([X -],+7)").expect("shouldn't error");
+    assert_eq!(first_note(&v).1.pitch,Pitch::Note(67));
+
+    let v = eval_val(&mut env,"trans(X,-12)").expect("shouldn't error");
+    assert!(matches!(v,Val::Trigger(Trigger{pitches,..}) if pitches == [Pitch::Note(48)]));
+    assert!(eval_val(&mut env,"trans(X,+100)").is_err());
+
+    let v = eval_val(&mut env,"vel([X -],0.5)").expect("shouldn't error");
+    assert_eq!(first_note(&v).1.vel,50);
+
+    let v = eval_val(&mut env,"vel(X,2)").expect("shouldn't error");
+    assert!(matches!(v,Val::Trigger(Trigger{vel:127,..})));
+
+    let v = eval_val(&mut env,"rev([(2: X -) -])").expect("shouldn't error");
+    let (step,n) = first_note(&v);
+    assert_eq!(step,1);
+    assert_eq!(n.sub,(1,2));
+
+    assert!(eval_val(&mut env,"rev(X)").is_err());
+  }
+}
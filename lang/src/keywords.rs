@@ -1,133 +1,254 @@
+use super::{Lexer,Token,parse_fsm,scales,funcs};
+use crate::ast::{Node,Stmt,Expr,Target};
+use crate::err::ErrorKind::{self,Unexpected,BadSymbol,InputEnded};
+
+//a number after a keyword, it can be any expression like "tempo * 2"
+//and gets checked when it runs, it just has to be there
+fn num(lx:&mut Lexer,missing:&'static str) -> Result<Node<Expr>,ErrorKind> {
+  match lx.lookahead() {
+    Token::Eol | Token::Eof => {
+      lx.advance();
+      Err(Unexpected(missing))
+    },
+    _ => parse_fsm::val(lx)
+  }
+}
+
+//"all" or some slots with commas between, "play 0..3,kick,9"
+fn slots(lx:&mut Lexer,missing:&'static str) -> Result<Node<Target>,ErrorKind> {
+  let start = lx.lookahead_span().start;
+  if lx.lookahead() == Token::All {
+    lx.advance();
+    return Ok(Node::new(Target::All,lx.span()));
+  }
 
-use super::{Lexer,Token,Runtime,Env,err};
+  let mut these = vec![num(lx,missing)?];
+  while lx.lookahead() == Token::Comma {
+    lx.advance();
+    these.push(num(lx,"we need another slot after the ','")?);
+  }
+  Ok(Node::new(Target::These(these),start..lx.span_end()))
+}
+
+pub fn bpm(lx:&mut Lexer) -> Result<Stmt,ErrorKind> {
+  lx.advance();
+
+  let bpm_num = num(lx,"expected a bpm value, like 120")?;
+  if lx.lookahead() != Token::Over {
+    return Ok(Stmt::Bpm(bpm_num,None));
+  }
+
+  lx.advance();
+  let bars = num(lx,"we need how many bars to get there in, like bpm 140 over 16")?;
+  Ok(Stmt::Bpm(bpm_num,Some(bars)))
+}
 
-pub fn bpm<RT:Runtime>(lx:&mut Lexer,env:&mut Env<RT>) -> Result<(),&'static str> {
+pub fn tap(lx:&mut Lexer) -> Result<Stmt,ErrorKind> {
   lx.advance();
+  Ok(Stmt::Tap)
+}
 
-  lx.expect(Token::Digits).map_err(|_|"expected a bpm value, like 120")?;
-  let bpm_num= lx.slice().parse::<usize>().map_err(|_|"couldn't parse the bpm number")?;
+pub fn div(lx:&mut Lexer) -> Result<Stmt,ErrorKind> {
+  lx.advance();
 
-  env.set_bpm(bpm_num);
-  Ok(())
+  let which = slots(lx,"we need a slot number")?;
+  let div_num = num(lx,"we need a time division")?;
+  Ok(Stmt::Div(which,div_num))
 }
 
-pub fn div<RT:Runtime>(lx:&mut Lexer,env:&mut Env<RT>) -> Result<(),&'static str> {
+pub fn sync(lx:&mut Lexer) -> Result<Stmt,ErrorKind> {
   lx.advance();
 
-  lx.expect(Token::Digits).map_err(|_|"we need a slot number")?;
-  let slot_num= lx.slice().parse::<usize>().map_err(|_|"couldn't parse this slot number")?;
-  lx.expect(Token::Digits).map_err(|_|"we need a time division")?;
-  let div_num= lx.slice().parse::<usize>().map_err(|_|"couldn't parse this time division")?;
+  let which = slots(lx,"we need a slot number")?;
+  let sync_num = num(lx,"we need a sync division")?;
+  Ok(Stmt::Sync(which,sync_num))
+}
+
+pub fn play(lx:&mut Lexer) -> Result<Stmt,ErrorKind> {
+  lx.advance();
 
-  env.set_div(slot_num,div_num);
-  Ok(())
+  let which = slots(lx,"we need a slot number")?;
+  Ok(Stmt::Play(which))
 }
 
-pub fn sync<RT:Runtime>(lx:&mut Lexer,env:&mut Env<RT>) -> Result<(),&'static str> {
+pub fn stop(lx:&mut Lexer) -> Result<Stmt,ErrorKind> {
   lx.advance();
 
-  lx.expect(Token::Digits).map_err(|_|"we need a slot number")?;
-  let slot_num= lx.slice().parse::<usize>().map_err(|_|"couldn't parse this slot number")?;
-  lx.expect(Token::Digits).map_err(|_|"we need a sync division")?;
-  let sync_num= lx.slice().parse::<usize>().map_err(|_|"couldn't parse the sync division")?;
+  let which = slots(lx,"we need a slot number")?;
+  Ok(Stmt::Stop(which))
+}
 
-  env.set_sync(slot_num,sync_num);
-  Ok(())
+pub fn list_outs(lx:&mut Lexer) -> Result<Stmt,ErrorKind> {
+  lx.advance();
+  Ok(Stmt::ListOuts)
 }
 
-pub fn play<RT:Runtime>(lx:&mut Lexer,env:&mut Env<RT>) -> Result<(),&'static str> {
+pub fn open_out(lx:&mut Lexer) -> Result<Stmt,ErrorKind> {
   lx.advance();
 
-  lx.expect(Token::Digits).map_err(|_|"we need a slot number")?;
-  let slot_num = lx.slice().parse::<usize>().map_err(|_|"couldn't parse this slot number")?;
+  let out_num = num(lx,"we need an output number")?;
+  let chan = num(lx,"we need a channel number")?;
+  Ok(Stmt::OpenOut(out_num,chan))
+}
+
+pub fn set_out(lx:&mut Lexer) -> Result<Stmt,ErrorKind> {
+  lx.advance();
 
-  env.play_slot(slot_num);
-  Ok(())
+  let which = slots(lx,"we need a slot number")?;
+  let out_num = num(lx,"we need an out_number")?;
+  Ok(Stmt::SetOut(which,out_num))
 }
 
-pub fn stop<RT:Runtime>(lx:&mut Lexer,env:&mut Env<RT>) -> Result<(),&'static str> {
+pub fn list_lps(lx:&mut Lexer) -> Result<Stmt,ErrorKind> {
   lx.advance();
+  Ok(Stmt::FindLps)
+}
 
-  lx.expect(Token::Digits).map_err(|_|"we need a slot number")?;
-  let slot_num = lx.slice().parse::<usize>().map_err(|_|"couldn't parse this slot number")?;
+pub fn open_lp(lx:&mut Lexer) -> Result<Stmt,ErrorKind> {
+  lx.advance();
 
-  env.stop_slot(slot_num);
-  Ok(())
+  let lp_num = num(lx,"we need a launchpad number")?;
+  Ok(Stmt::OpenLp(lp_num))
 }
 
-pub fn list_outs<RT:Runtime>(lx:&mut Lexer,env:&mut Env<RT>) -> Result<(),&'static str> {
+pub fn clock_out(lx:&mut Lexer) -> Result<Stmt,ErrorKind> {
   lx.advance();
-  env.list_outs();
-  Ok(())
+
+  let out_num = num(lx,"we need an output number")?;
+  let on = match lx.next() {
+    Token::Sym if lx.slice().eq_ignore_ascii_case("on") => true,
+    Token::Sym if lx.slice().eq_ignore_ascii_case("off") => false,
+    Token::Err => return Err(BadSymbol),
+    _ => return Err(Unexpected("we need 'on' or 'off' for the clock"))
+  };
+  Ok(Stmt::ClockOut(out_num,Node::new(on,lx.span())))
 }
 
-pub fn open_out<RT:Runtime>(lx:&mut Lexer,env:&mut Env<RT>) -> Result<(),&'static str> {
+pub fn list_ins(lx:&mut Lexer) -> Result<Stmt,ErrorKind> {
   lx.advance();
+  Ok(Stmt::ListIns)
+}
 
-  lx.expect(Token::Digits).map_err(|_|"we need an output number")?;
-  let out_num = lx.slice().parse::<usize>().map_err(|_|"couldn't parse this output num")?;
+pub fn clock_in(lx:&mut Lexer) -> Result<Stmt,ErrorKind> {
+  lx.advance();
 
-  lx.expect(Token::Digits).map_err(|_|"we need a channel number")?;
-  let chan = lx.slice().parse::<u8>().map_err(|_|"couldn't parse this channel num")?;
-  if chan > 15 {
-    return Err("channel is out of range, must be 0 - 15");
+  if lx.lookahead() == Token::Sym && lx.src()[lx.lookahead_span()].eq_ignore_ascii_case("off") {
+    lx.advance();
+    return Ok(Stmt::ClockIn(None));
   }
 
-  env.open_out(out_num,chan);
-  Ok(())
+  let in_num = num(lx,"we need an input number, or 'off'")?;
+  Ok(Stmt::ClockIn(Some(in_num)))
 }
 
-pub fn set_out<RT:Runtime>(lx:&mut Lexer,env:&mut Env<RT>) -> Result<(),&'static str> {
+pub fn clock_stats(lx:&mut Lexer) -> Result<Stmt,ErrorKind> {
   lx.advance();
+  Ok(Stmt::ClockStats)
+}
 
-  lx.expect(Token::Digits).map_err(|_|"we need a slot number")?;
-  let slot_num= lx.slice().parse::<usize>().map_err(|_|"couldn't parse this slot number")?;
-  lx.expect(Token::Digits).map_err(|_|"we need an out_number")?;
-  let sync_num= lx.slice().parse::<usize>().map_err(|_|"couldn't parse the out numver")?;
+pub fn pause(lx:&mut Lexer) -> Result<Stmt,ErrorKind> {
+  lx.advance();
+  Ok(Stmt::Pause)
+}
 
-  env.set_output(slot_num,sync_num);
-  Ok(())
+pub fn resume(lx:&mut Lexer) -> Result<Stmt,ErrorKind> {
+  lx.advance();
+  Ok(Stmt::Continue)
 }
 
-pub fn list_lps<RT:Runtime>(lx:&mut Lexer,env:&mut Env<RT>) -> Result<(),&'static str> {
+pub fn restart(lx:&mut Lexer) -> Result<Stmt,ErrorKind> {
   lx.advance();
-  env.list_lps();
-  Ok(())
+  Ok(Stmt::Restart)
 }
 
-pub fn open_lp<RT:Runtime>(lx:&mut Lexer,env:&mut Env<RT>) -> Result<(),&'static str> {
+pub fn locate(lx:&mut Lexer) -> Result<Stmt,ErrorKind> {
   lx.advance();
 
-  lx.expect(Token::Digits).map_err(|_|"we need a launchpad number")?;
-  let lp_num = lx.slice().parse::<usize>().map_err(|_|"couldn't parse this launchpad number")?;
-  env.open_lp(lp_num);
-  Ok(())
+  let bar = num(lx,"we need a bar to go to, like locate 8")?;
+  Ok(Stmt::Locate(bar))
 }
 
-pub fn do_file<RT:Runtime>(lx:&mut Lexer,env:&mut Env<RT>) -> Result<(),err::ParseError> {
+pub fn do_file(lx:&mut Lexer) -> Result<Stmt,ErrorKind> {
   lx.advance();
 
-  let start = match lx.lookahead() {
-    Token::Eof | Token::Eol => return Err(err::ParseError::Msg("we need a path to the file")),
-    _ => {
+  match lx.lookahead() {
+    Token::Path => {
       lx.advance();
-      lx.span_start()
-    }
-  };
+      Ok(Stmt::DoFile(Node::new(lx.slice().to_string(),lx.span())))
+    },
+    _ => Err(Unexpected("we need a path to the file"))
+  }
+}
 
-  let end = loop {
-    lx.advance();
-    match lx.lookahead() {
-      Token::Eof | Token::Eol => {
-        break lx.span_end();
+
+//key D dorian, key Eb minor or key D#2 lydian, without an octave the root is in octave 3
+pub fn key(lx:&mut Lexer) -> Result<Stmt,ErrorKind> {
+  lx.advance();
+  let start = lx.lookahead_span().start;
+
+  let root = match lx.next() {
+    Token::Note => parse_fsm::note_to_u8(lx.slice())?,
+    Token::Sym => {
+      let txt = lx.slice();
+      let valid = matches!(txt.as_bytes(),[b'a'..=b'g' | b'A'..=b'G'] | [b'a'..=b'g' | b'A'..=b'G',b'b']);
+      if !valid {
+        return Err(Unexpected("we need a root note for the key, like D or Eb or F#2"));
       }
-      _ => ()
-    }
+      parse_fsm::midi_note(parse_fsm::pitch_class(txt),3)?
+    },
+    _ => return Err(Unexpected("we need a root note for the key, like D or Eb or F#2"))
   };
 
+  lx.expect(Token::Sym).map_err(|_|Unexpected("we need a scale for the key, like major or dorian"))?;
+  let steps = scales::scale(lx.slice()).ok_or_else(||{
+    ErrorKind::UnknownScale{suggestions:scales::similar(lx.slice())}
+  })?;
+
+  Ok(Stmt::Key(Node::new(scales::Key{root,steps},start..lx.span_end())))
+}
+
+//slot kick = 0, then "kick" works anywhere a slot number does
+pub fn slot(lx:&mut Lexer) -> Result<Stmt,ErrorKind> {
   lx.advance();
 
-  env.do_file(&lx.src()[start..end])?;
-  Ok(())
+  parse_fsm::name(lx,"we need a name for the slot, like slot kick = 0")?;
+  let nm = Node::new(lx.slice().to_string(),lx.span());
+  lx.expect(Token::Eq).map_err(|_|Unexpected("we need an '=' and then the slot number"))?;
+
+  let snum = num(lx,"we need a slot number")?;
+  Ok(Stmt::SlotName(nm,snum))
 }
 
+//def fill(a,b) = [a a b -], calling fill(X,C) runs the body with a and b set
+pub fn def(lx:&mut Lexer) -> Result<Stmt,ErrorKind> {
+  lx.advance();
+
+  parse_fsm::name(lx,"we need a name for the def")?;
+  let nm = Node::new(lx.slice().to_string(),lx.span());
+  if funcs::NAMES.contains(&nm.item.as_str()) {
+    return Err(Unexpected("there's already a builtin function with this name"));
+  }
+
+  lx.expect(Token::OpenParen).map_err(|_|Unexpected("we need a '(' with the params, like def fill(a,b) = ..."))?;
+  let mut params = vec![];
+  if lx.lookahead() == Token::CloseParen {
+    lx.advance();
+  }
+  else {
+    loop {
+      parse_fsm::name(lx,"the params of a def have to be names")?;
+      params.push(Node::new(lx.slice().to_string(),lx.span()));
+      match lx.next() {
+        Token::Comma => (),
+        Token::CloseParen => break,
+        Token::Err => return Err(BadSymbol),
+        Token::Eof => return Err(InputEnded),
+        _ => return Err(Unexpected("we need a ',' between params or a ')' to finish them"))
+      }
+    }
+  }
+
+  lx.expect(Token::Eq).map_err(|_|Unexpected("we need an '=' after the params of a def"))?;
+  let body = parse_fsm::val(lx)?;
+  Ok(Stmt::Def(nm,params,body))
+}
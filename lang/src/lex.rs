@@ -2,6 +2,9 @@ use logos::Logos;
 
 #[derive(Logos,Copy,Clone,Debug,PartialEq,Eq)]
 #[logos(skip r"[ \t\f]+")] // Ignore this regex pattern between tokens
+#[logos(skip r"(#|//)[^\n]*")] // line comments
+#[logos(skip r"/\*[^*]*\*+([^/*][^*]*\*+)*/")] // block comments
+#[logos(skip r"\\[ \t\f]*\r?\n")] // a \ at the end of a line carries on to the next one
 pub enum Token {
   //KEYWORDZ
   #[token("bpm",ignore(case))]
@@ -34,9 +37,58 @@ pub enum Token {
   #[token("open_lp",ignore(case))]
   OpenLp,
 
+  #[token("clock_out",ignore(case))]
+  ClockOut,
+
+  #[token("list_ins",ignore(case))]
+  ListIns,
+
+  #[token("clock_in",ignore(case))]
+  ClockIn,
+
+  #[token("clock_stats",ignore(case))]
+  ClockStats,
+
   #[token("do_file",ignore(case))]
   DoFile,
 
+  #[token("key",ignore(case))]
+  Key,
+
+  #[token("def",ignore(case))]
+  Def,
+
+  #[token("slot",ignore(case))]
+  Slot,
+
+  #[token("all",ignore(case))]
+  All,
+
+  #[token("over",ignore(case))]
+  Over,
+
+  #[token("tap",ignore(case))]
+  Tap,
+
+  #[token("pause",ignore(case))]
+  Pause,
+
+  #[token("continue",ignore(case))]
+  Continue,
+
+  #[token("restart",ignore(case))]
+  Restart,
+
+  #[token("locate",ignore(case))]
+  Locate,
+
+  #[token("for",ignore(case))]
+  For,
+
+  //short words lose to Sym unless they get a push
+  #[token("in",priority=10,ignore(case))]
+  In,
+
   //--------
 
   #[token("(")]
@@ -68,25 +120,78 @@ pub enum Token {
 
   #[token("-")]
   Dash,
+
+  #[token("?")]
+  Quest,
+
+  #[token("|")]
+  Pipe,
+
+  #[token(",")]
+  Comma,
+
+  #[token("+")]
+  Plus,
+
+  #[token(":")]
+  Colon,
+
+  #[token("*")]
+  Star,
+
+  //always dividing, "tempo/2" and "tempo / 2" are the same. only the rest
+  //of the line after do_file is a path
+  #[token("/")]
+  Slash,
   
+  //a range of numbers, both ends are in it
+  #[token("..")]
+  DotDot,
+
+  //a ; does the same as starting a new line
+  #[token(";")]
+  Semi,
+
   #[token("\n")]
   Eol,
 
   #[regex(r"[abcdefgABCDEFG][\_#b][0-8]")]
   Note,
 
+  #[regex(r"[abcdefgABCDEFG][#b]?(maj7|maj|min|m7|7|dim|sus2|sus4)@[0-8]")]
+  Chord,
+
+  #[regex(r"\^[1-9]\d*(\++|-+)?")]
+  Degree,
+
   #[regex(r"\d+", priority=10)]
   Digits,
 
+  #[regex(r"\d+\.\d+")]
+  Decimal,
+
   #[regex(r"[\d\w]+", priority=5)]
   Sym,
 
+  //no escapes, a string is everything up to the next quote on the line
+  #[regex(r#""[^"\n]*""#)]
+  Str,
+
   //these are things the lexer will never
-  //match
+  //match, a Path is the rest of the line after do_file
+  //and only the Lexer below knows about that
+  Path,
   Err,
   Eof
 }
 
+//the words the lexer turns into keyword tokens, for completion and the like
+pub const KEYWORDS : &[&str] = &[
+  "bpm","div","sync","play","stop","list_outs","open_output",
+  "set_output","find_lps","open_lp","clock_out","list_ins","clock_in","clock_stats","do_file","key","def","for","in","all","slot","over","tap",
+  "pause","continue","restart","locate"
+];
+
 pub enum ExpectErr {
   Eof,
   LexErr,
@@ -94,11 +199,12 @@ pub enum ExpectErr {
 }
 
 
-#[derive(Debug)]
+#[derive(Debug,Clone)]
 pub struct Lexer<'a> {
   lx:logos::Lexer<'a,Token>,
   cspan:std::ops::Range<usize>,
-  current:Token
+  current:Token,
+  lspan:std::ops::Range<usize>
 }
 
 impl<'a> Lexer<'a> {
@@ -113,12 +219,29 @@ impl<'a> Lexer<'a> {
     };
 
     Self {
-      lx,
+      lspan:lx.span(),
       cspan:sp,
-      current:first
+      current:first,
+      lx
     }
   }
 
+  //the token after the current one, which is a Path if the current one is do_file
+  fn pull(&mut self) -> (Token,std::ops::Range<usize>) {
+    let from = self.lspan.end;
+    if self.current == Token::DoFile && let Some(span) = path_span(self.lx.source(),from) {
+      self.lx.bump(span.end - from);
+      return (Token::Path,span);
+    }
+
+    let t = match self.lx.next() {
+      Some(Ok(t)) => t,
+      Some(Err(_)) => Token::Err,
+      None => Token::Eof
+    };
+    (t,self.lx.span())
+  }
+
   pub fn lookahead(&self) -> Token {
     self.current
   }
@@ -131,6 +254,10 @@ impl<'a> Lexer<'a> {
     self.cspan.clone()
   }
 
+  pub fn lookahead_span(&self) -> std::ops::Range<usize> {
+    self.lspan.clone()
+  }
+
   pub fn span_start(&self) -> usize {
     self.cspan.start
   }
@@ -149,13 +276,8 @@ impl<'a> Lexer<'a> {
 
   pub fn next(&mut self) -> Token {
     let out = self.current;
-    self.cspan = self.lx.span();
-
-    self.current = match self.lx.next() {
-      Some(Ok(t)) => t,
-      Some(Err(_)) => Token::Err,
-      None => Token::Eof
-    };
+    self.cspan = self.lspan.clone();
+    (self.current,self.lspan) = self.pull();
     out
   }
 
@@ -176,7 +298,35 @@ impl<'a> Lexer<'a> {
   }
 }
 
+//a path can have spaces and slashes in it so it goes to the end of the line,
+//minus a comment that has a space before it like "./a.sgv # the intro"
+fn path_span(src:&str,from:usize) -> Option<std::ops::Range<usize>> {
+  let rest = &src[from..];
+  let line = &rest[..rest.find('\n').unwrap_or(rest.len())];
+  let cut = line.char_indices()
+  .find(|(i,_)|line[..*i].ends_with(char::is_whitespace) && ["#","//","/*"].iter().any(|c|line[*i..].starts_with(c)))
+  .map(|(i,_)|i)
+  .unwrap_or(line.len());
+
+  let path = line[..cut].trim_end();
+  let start = path.len() - path.trim_start().len();
+  match start < path.len() {
+    true => Some(from + start..from + path.len()),
+    false => None
+  }
+}
+
 impl Token {
+  pub fn is_keyword(self) -> bool {
+    matches!(self,
+      Token::Bpm | Token::Div | Token::Sync | Token::Play | Token::Stop | Token::ListOuts |
+      Token::OpenOut | Token::SetOut | Token::FindLps | Token::OpenLp | Token::ClockOut |
+      Token::ListIns | Token::ClockIn | Token::ClockStats | Token::DoFile | Token::Key | Token::Def |
+      Token::For | Token::In | Token::All | Token::Slot | Token::Over | Token::Tap |
+      Token::Pause | Token::Continue | Token::Restart | Token::Locate
+    )
+  }
+
   pub fn parse(self, mut lx:Lexer) -> Result<((Token,std::ops::Range<usize>),Lexer),Lexer> {
     match lx.next() {
       t if t == self => Ok(( (t,lx.span()),lx )),
@@ -191,7 +341,7 @@ mod tests {
 
   #[test]
   fn test_lexer() {
-    let input = "sym [ ] doookz > #";
+    let input = "sym [ ] doookz > $";
     let mut mylex = Lexer::new(input);
     let mut lx = Token::lexer(input);
 
@@ -248,9 +398,55 @@ mod tests {
     let mut lxr = Token::lexer(input);
     assert!(matches!(lxr.next(),Some(Ok(Token::Bpm))));
 
-    let input = "/fish/wish";
-    let mut lxr = Token::lexer(input);
-    let nxt = lxr.next();
-    assert!(matches!(nxt,Some(Ok(Token::Path))));
+    let mut lx = Lexer::new("do_file /fish/wish");
+    assert_eq!(lx.next(),Token::DoFile);
+    assert_eq!(lx.next(),Token::Path);
+    assert_eq!(lx.slice(),"/fish/wish");
+    assert_eq!(lx.next(),Token::Eof);
+
+    for kw in KEYWORDS.iter() {
+      let mut lxr = Token::lexer(kw);
+      assert!(lxr.next().is_some_and(|t|t.is_ok_and(Token::is_keyword)));
+      assert!(lxr.next().is_none());
+    }
+  }
+
+  #[test]
+  fn test_comments() {
+    let toks = |input| Token::lexer(input).map(|t|t.expect(input)).collect::<Vec<_>>();
+
+    assert_eq!(toks("X = (C#1 127) # a kick"),toks("X = (C#1 127)"));
+    assert_eq!(toks("X = (C#1 127) // a kick\nplay 0"),toks("X = (C#1 127)\nplay 0"));
+    assert_eq!(toks("# just a comment"),[]);
+    assert_eq!(toks("/* a */"),[]);
+    assert_eq!(toks("/**/"),[]);
+    assert_eq!(toks("[X - /* a\n long one */ C -]"),toks("[X - C -]"));
+    assert_eq!(toks("[X - \\\n  C -]"),toks("[X - C -]"));
+    assert_eq!(toks("[X - \\  \r\n  C -]"),toks("[X - C -]"));
+    assert_eq!(toks("a = \"kick # drum\" / 2"),[Token::Sym,Token::Eq,Token::Str,Token::Slash,Token::Digits]);
+    assert_eq!(toks("for i in 0..8 { play i; } # ../x"),[
+      Token::For,Token::Sym,Token::In,Token::Digits,Token::DotDot,Token::Digits,
+      Token::OpenCrl,Token::Play,Token::Sym,Token::Semi,Token::CloseCrl
+    ]);
+  }
+
+  #[test]
+  fn test_paths() {
+    let toks = |input| {
+      let mut lx = Lexer::new(input);
+      let mut out = vec![];
+      while lx.lookahead() != Token::Eof {
+        out.push((lx.next(),lx.slice().to_string()));
+      }
+      out
+    };
+    let path = |p:&str| (Token::Path,p.to_string());
+
+    //only a do_file gets a path, anywhere else a slash is division
+    assert_eq!(toks("bpm tempo/2").into_iter().map(|(t,_)|t).collect::<Vec<_>>(),[Token::Bpm,Token::Sym,Token::Slash,Token::Digits]);
+    assert_eq!(toks("do_file ./sets/one.sgv # the first set")[1..],[path("./sets/one.sgv")]);
+    assert_eq!(toks("do_file ~/my sets/a//b.sgv // two")[1..],[path("~/my sets/a//b.sgv")]);
+    assert_eq!(toks("do_file sets/one.sgv\nbpm 2/1")[1..3],[path("sets/one.sgv"),(Token::Eol,"\n".to_string())]);
+    assert_eq!(toks("do_file # nothing").into_iter().map(|(t,_)|t).collect::<Vec<_>>(),[Token::DoFile]);
   }
 }
@@ -6,60 +6,185 @@ mod seq_types;
 mod lex;
 mod bar;
 mod keywords;
+mod funcs;
+mod scales;
 mod parse_fsm;
+mod eval;
+mod fmt;
+pub mod ast;
 
 pub use {
   env::Env,
-  err::Error,
-  seq_types::SeqNote,
-  lex::{Token,Lexer,ExpectErr},
+  err::{Error,ErrorKind},
+  seq_types::{SeqNote,Pitch},
+  scales::Key,
+  lex::{Token,Lexer,ExpectErr,KEYWORDS},
+  funcs::NAMES as FUNCTIONS,
+  fmt::format,
 };
 
+//how many slots the sequencer has, a slot number has to be less than this
+pub const SLOT_COUNT : usize = 64;
+
+//the slots a keyword is for, only the runtime knows how many "all" is
+#[derive(Debug,Clone,PartialEq)]
+pub enum Slots {
+  All,
+  These(Vec<usize>)
+}
+
+impl Slots {
+  pub fn nums(&self,count:usize) -> Vec<usize> {
+    match self {
+      Slots::All => (0..count).collect(),
+      Slots::These(ns) => ns.iter().copied().filter(|n|*n < count).collect()
+    }
+  }
+}
+
+//the keywords that take slots hand them over all at once,
+//so "play 0..7" can start everything on the same tick
 pub trait Runtime {
   fn assign_slot(&mut self,snum:usize,len:usize,evs:HashMap<usize,Vec<SeqNote>>);
-  fn set_bpm(&mut self,bpm_num:usize);
-  fn set_div(&mut self,slot:usize,div_num:usize);
-  fn set_sync(&mut self,slot:usize,sync_num:usize);
-  fn set_output(&mut self,slot:usize,out_num:usize);
-  fn play_slot(&mut self,slot:usize);
-  fn stop_slot(&mut self,slot:usize);
-  fn list_outs(&mut self);
-  fn list_lps(&mut self);
+  fn set_bpm(&mut self,bpm_num:f64);
+  //gets to the new bpm a bit at a time over some bars
+  fn ramp_bpm(&mut self,bpm_num:f64,bars:usize);
+  fn tap(&mut self);
+  fn set_div(&mut self,slots:&Slots,div_num:usize);
+  fn set_sync(&mut self,slots:&Slots,sync_num:usize);
+  fn set_output(&mut self,slots:&Slots,out_num:usize);
+  fn play_slots(&mut self,slots:&Slots);
+  fn stop_slots(&mut self,slots:&Slots);
+  //the listings only print, a runtime with nothing plugged in can skip them
+  fn list_outs(&mut self) {}
+  fn list_lps(&mut self) {}
   fn open_out(&mut self,out_num:usize,channel:u8);
   fn open_lp(&mut self,lp_num:usize);
+  fn clock_out(&mut self,out_num:usize,on:bool);
+  fn list_ins(&mut self) {}
+  //None goes back to our own clock
+  fn clock_in(&mut self,in_num:Option<usize>);
+  fn clock_stats(&mut self) {}
+  //the whole song, every slot keeps its place while it's paused
+  fn pause(&mut self);
+  fn resume(&mut self);
+  fn restart(&mut self);
+  fn locate(&mut self,bar:usize);
+  fn set_key(&mut self,key:Key);
+  //the name only goes with one slot, any other slot that had it loses it
+  fn name_slot(&mut self,slot:usize,nm:&str);
 }
 
-pub fn parse<'a,T:Runtime>(src:&'a str, env:&mut Env<T>) -> Result<(),err::Error<'a>> {
+//everything that did parse along with an error for each statement
+//that didn't, editors want to keep going with whatever is there
+pub fn parse_partial(src:&str) -> (Vec<ast::Node<ast::Stmt>>,Vec<err::Error<'_>>) {
   let mut lx = Lexer::new(src);
-  match parse_fsm::root(&mut lx,env) {
-    Ok(()) => Ok(()),
-    Err(err::ParseError::Msg(msg)) => {
-      let (src,s,e) = lx.done();
-      Err(Error::Root(src,s,e,msg))
-    },
-    Err(err::ParseError::SubFile(msg,subtxt,s,e)) => { 
-      let (src,ns,ne) = lx.done();
-      Err(Error::Sub(&src[ns..ne],subtxt,s,e,msg))
-    }
+  let (tree,errs) = parse_fsm::root(&mut lx);
+  let errs = errs.into_iter().map(|e|Error::Root(src,e.span.start,e.span.end,e.item)).collect();
+  (tree,errs)
+}
+
+//just the tree, nothing in the env or the runtime gets touched.
+//every statement that doesn't parse gets its own error
+pub fn parse_tree(src:&str) -> Result<Vec<ast::Node<ast::Stmt>>,Vec<err::Error<'_>>> {
+  match parse_partial(src) {
+    (tree,errs) if errs.is_empty() => Ok(tree),
+    (_,errs) => Err(errs)
   }
 }
 
+//runs a tree that came from parse_tree(src)
+pub fn eval<'a,T:Runtime>(src:&'a str,tree:&[ast::Node<ast::Stmt>],env:&mut Env<T>) -> Result<(),Vec<err::Error<'a>>> {
+  eval::run(tree,env).map_err(|errs|{
+    errs.into_iter().map(|e|match e {
+      err::EvalError::At(msg,span) => Error::Root(src,span.start,span.end,msg),
+      err::EvalError::SubFile(path,subtxt,span,msg) => Error::Sub(path,subtxt,span.start,span.end,msg)
+    }).collect()
+  })
+}
+
+pub fn parse<'a,T:Runtime>(src:&'a str, env:&mut Env<T>) -> Result<(),Vec<err::Error<'a>>> {
+  let tree = parse_tree(src)?;
+  eval(src,&tree,env)
+}
+
+//a runtime that doesn't do anything, for when only the names and values matter
+//like in an editor
+pub struct NullRuntime;
+
+impl Runtime for NullRuntime {
+  fn assign_slot(&mut self,_:usize,_:usize,_:HashMap<usize,Vec<SeqNote>>) {}
+  fn set_bpm(&mut self,_:f64) {}
+  fn ramp_bpm(&mut self,_:f64,_:usize) {}
+  fn tap(&mut self) {}
+  fn set_div(&mut self,_:&Slots,_:usize) {}
+  fn set_sync(&mut self,_:&Slots,_:usize) {}
+  fn set_output(&mut self,_:&Slots,_:usize) {}
+  fn play_slots(&mut self,_:&Slots) {}
+  fn stop_slots(&mut self,_:&Slots) {}
+  fn list_outs(&mut self) {}
+  fn list_lps(&mut self) {}
+  fn open_out(&mut self,_:usize,_:u8) {}
+  fn open_lp(&mut self,_:usize) {}
+  fn clock_out(&mut self,_:usize,_:bool) {}
+  fn list_ins(&mut self) {}
+  fn clock_in(&mut self,_:Option<usize>) {}
+  fn clock_stats(&mut self) {}
+  fn pause(&mut self) {}
+  fn resume(&mut self) {}
+  fn restart(&mut self) {}
+  fn locate(&mut self,_:usize) {}
+  fn set_key(&mut self,_:Key) {}
+  fn name_slot(&mut self,_:usize,_:&str) {}
+}
+
+#[cfg(test)]
 mod test_utils {
   use super::*;
 
-  pub struct NullRt{}
-
-  impl Runtime for NullRt {
-    fn assign_slot(&mut self,_:usize,_:usize,_:HashMap<usize,Vec<SeqNote>>) {}
-    fn set_bpm(&mut self,_:usize) {}
-    fn set_div(&mut self,_:usize,_:usize) {}
-    fn set_sync(&mut self,_:usize,_:usize) {}
-    fn set_output(&mut self,_:usize,_:usize) {}
-    fn play_slot(&mut self,_:usize) {}
-    fn stop_slot(&mut self,_:usize) {}
-    fn list_outs(&mut self) {}
-    fn list_lps(&mut self) {}
-    fn open_out(&mut self,_:usize,_:u8) {}
-    fn open_lp(&mut self,_:usize) {}
+  //writes down what it got asked to do, like "div 0 3"
+  #[derive(Default)]
+  pub struct LogRt(pub Vec<String>);
+
+  impl Runtime for LogRt {
+    fn assign_slot(&mut self,snum:usize,len:usize,_:HashMap<usize,Vec<SeqNote>>) { self.0.push(format!("slot {} {}",snum,len)) }
+    fn set_bpm(&mut self,bpm_num:f64) { self.0.push(format!("bpm {}",bpm_num)) }
+    fn ramp_bpm(&mut self,bpm_num:f64,bars:usize) { self.0.push(format!("bpm {} over {}",bpm_num,bars)) }
+    fn tap(&mut self) { self.0.push("tap".to_string()) }
+    fn set_div(&mut self,slots:&Slots,div_num:usize) { self.0.push(format!("div {} {}",txt(slots),div_num)) }
+    fn set_sync(&mut self,slots:&Slots,sync_num:usize) { self.0.push(format!("sync {} {}",txt(slots),sync_num)) }
+    fn set_output(&mut self,slots:&Slots,out_num:usize) { self.0.push(format!("set_output {} {}",txt(slots),out_num)) }
+    fn play_slots(&mut self,slots:&Slots) { self.0.push(format!("play {}",txt(slots))) }
+    fn stop_slots(&mut self,slots:&Slots) { self.0.push(format!("stop {}",txt(slots))) }
+    fn list_outs(&mut self) { self.0.push("list_outs".to_string()) }
+    fn list_lps(&mut self) { self.0.push("find_lps".to_string()) }
+    fn open_out(&mut self,out_num:usize,channel:u8) { self.0.push(format!("open_output {} {}",out_num,channel)) }
+    fn open_lp(&mut self,lp_num:usize) { self.0.push(format!("open_lp {}",lp_num)) }
+    fn clock_out(&mut self,out_num:usize,on:bool) { self.0.push(format!("clock_out {} {}",out_num,if on {"on"} else {"off"})) }
+    fn list_ins(&mut self) { self.0.push("list_ins".to_string()) }
+    fn clock_in(&mut self,in_num:Option<usize>) { self.0.push(format!("clock_in {}",in_num.map(|n|n.to_string()).unwrap_or("off".into()))) }
+    fn clock_stats(&mut self) { self.0.push("clock_stats".to_string()) }
+    fn pause(&mut self) { self.0.push("pause".to_string()) }
+    fn resume(&mut self) { self.0.push("continue".to_string()) }
+    fn restart(&mut self) { self.0.push("restart".to_string()) }
+    fn locate(&mut self,bar:usize) { self.0.push(format!("locate {}",bar)) }
+    fn set_key(&mut self,key:Key) { self.0.push(format!("key {}",key)) }
+    fn name_slot(&mut self,slot:usize,nm:&str) { self.0.push(format!("slot {} = {}",nm,slot)) }
+  }
+
+  fn txt(slots:&Slots) -> String {
+    match slots {
+      Slots::All => "all".to_string(),
+      Slots::These(ns) => ns.iter().map(|n|n.to_string()).collect::<Vec<_>>().join(",")
+    }
+  }
+
+  //parses one value and runs it, any error just turns into its kind
+  pub fn eval_val<T:Runtime>(env:&mut Env<T>,src:&str) -> Result<seq_types::Val,ErrorKind> {
+    let mut lx = Lexer::new(src);
+    let tree = parse_fsm::val(&mut lx)?;
+    eval::expr(&tree,env).map_err(|e|match e {
+      err::EvalError::At(kind,_) | err::EvalError::SubFile(_,_,_,kind) => kind
+    })
   }
 }
@@ -1,172 +1,489 @@
-use super::{Lexer,Token,Runtime,Env,bar,keywords,err};
-use crate::seq_types::{Val,Trigger,LineBuilder};
+use super::{Lexer,Token,ExpectErr,KEYWORDS,bar,keywords};
+use crate::ast::{Node,Stmt,Expr,Op,Steps,Assign};
+use crate::seq_types::{Trigger,Pitch};
+use crate::err::ErrorKind::{self,Unexpected,BadSymbol,InputEnded,OutOfRange};
 
-pub fn root<RT:Runtime>(lx:&mut Lexer,env:&mut Env<RT>) -> Result<(),err::ParseError> {
+//the whole input as a list of statements, nothing gets run here.
+//a bad statement gets skipped so we can keep looking for more mistakes
+pub fn root(lx:&mut Lexer) -> (Vec<Node<Stmt>>,Vec<Node<ErrorKind>>) {
+  let mut stmts = vec![];
+  let mut errs = vec![];
   loop {
-    match lx.lookahead() {
-      Token::Eol => { 
-        lx.advance();
+    while matches!(lx.lookahead(),Token::Eol | Token::Semi) {
+      lx.advance();
+    }
+
+    if lx.lookahead() == Token::Eof {
+      return (stmts,errs);
+    }
+
+    let start = lx.lookahead_span().start;
+    match statement(lx) {
+      Ok(stmt) => stmts.push(Node::new(stmt,start..lx.span_end())),
+      Err(msg) => {
+        errs.push(Node::new(msg,lx.span()));
+        recover(lx,start);
       }
-      Token::Sym | Token::Digits => {
-        lx.advance();
-        assignment(lx,env)?
-      },
-      Token::OpenAng => slot_assignment(lx,env)?,
-      Token::Bpm => keywords::bpm(lx,env)?,
-      Token::Div => keywords::div(lx,env)?,
-      Token::Sync => keywords::sync(lx,env)?,
-      Token::Play => keywords::play(lx,env)?,
-      Token::Stop => keywords::stop(lx,env)?,
-      Token::ListOuts => keywords::list_outs(lx,env)?,
-      Token::OpenOut => keywords::open_out(lx,env)?,
-      Token::SetOut => keywords::set_out(lx,env)?,
-      Token::FindLps => keywords::list_lps(lx,env)?,
-      Token::OpenLp => keywords::open_lp(lx,env)?,
-      Token::DoFile => keywords::do_file(lx,env)?,
-
-      Token::Eof => return Ok(()),
-      _ => return Err("unknown symbol".into())
     }
   }
 }
 
-fn assignment<RT:Runtime>(lx:&mut Lexer,env:&mut Env<RT>) -> Result<(),&'static str> {
-  let nm_span = lx.span();
+fn statement(lx:&mut Lexer) -> Result<Stmt,ErrorKind> {
+  match lx.lookahead() {
+    //"tap = 3" reads fine but tap is taken, better to say so than complain about the =
+    t if t.is_keyword() && assigns(lx) => {
+      lx.advance();
+      Err(keyword(lx))
+    },
+    Token::Sym | Token::Digits => {
+      lx.advance();
+      let (nm,v) = assignment(lx)?;
+      Ok(Stmt::Assign(nm,v))
+    },
+    Token::OpenAng => slot_assignment(lx),
+    Token::Bpm => keywords::bpm(lx),
+    Token::Tap => keywords::tap(lx),
+    Token::Div => keywords::div(lx),
+    Token::Sync => keywords::sync(lx),
+    Token::Play => keywords::play(lx),
+    Token::Stop => keywords::stop(lx),
+    Token::ListOuts => keywords::list_outs(lx),
+    Token::OpenOut => keywords::open_out(lx),
+    Token::SetOut => keywords::set_out(lx),
+    Token::FindLps => keywords::list_lps(lx),
+    Token::OpenLp => keywords::open_lp(lx),
+    Token::ClockOut => keywords::clock_out(lx),
+    Token::ListIns => keywords::list_ins(lx),
+    Token::ClockIn => keywords::clock_in(lx),
+    Token::ClockStats => keywords::clock_stats(lx),
+    Token::Pause => keywords::pause(lx),
+    Token::Continue => keywords::resume(lx),
+    Token::Restart => keywords::restart(lx),
+    Token::Locate => keywords::locate(lx),
+    Token::DoFile => keywords::do_file(lx),
+    Token::Key => keywords::key(lx),
+    Token::Def => keywords::def(lx),
+    Token::For => for_loop(lx),
+    Token::Slot => keywords::slot(lx),
+    _ => {
+      lx.advance();
+      Err(Unexpected("unknown symbol"))
+    }
+  }
+}
+
+fn assigns(lx:&Lexer) -> bool {
+  let mut peek = lx.clone();
+  peek.advance();
+  peek.lookahead() == Token::Eq
+}
+
+//the keyword that was just read, used where a name should have been
+pub fn keyword(lx:&Lexer) -> ErrorKind {
+  let kw = KEYWORDS.iter().find(|k|k.eq_ignore_ascii_case(lx.slice())).copied().unwrap_or("this");
+  ErrorKind::Keyword(kw)
+}
+
+//a name has to come next, missing is the error for anything but a keyword
+pub fn name(lx:&mut Lexer,missing:&'static str) -> Result<(),ErrorKind> {
+  match lx.expect(Token::Sym) {
+    Ok(()) => Ok(()),
+    Err(ExpectErr::WrongToken(t)) if t.is_keyword() => Err(keyword(lx)),
+    Err(_) => Err(Unexpected(missing))
+  }
+}
+
+//skips to the end of the line with the mistake on it, unless the mistake is inside
+//brackets that go over a few lines, then it skips to the end of the line they close on
+fn recover(lx:&mut Lexer,start:usize) {
+  let mut depth = 0;
+  let mut sub_lx = Lexer::new(&lx.src()[start..lx.span_end()]);
+  let mut last = Token::Eof;
+  while sub_lx.lookahead() != Token::Eof {
+    last = sub_lx.next();
+    depth += nesting(last);
+  }
+
+  //the mistake might have been the end of the line already
+  if last == Token::Eol && depth <= 0 {
+    return;
+  }
+
+  loop {
+    match lx.lookahead() {
+      Token::Eof => return,
+      Token::Eol if depth <= 0 => return,
+      t => depth += nesting(t)
+    }
+    lx.advance();
+  }
+}
+
+fn nesting(t:Token) -> isize {
+  match t {
+    Token::OpenParen | Token::OpenSq | Token::OpenCrl => 1,
+    Token::CloseParen | Token::CloseSq | Token::CloseCrl => -1,
+    _ => 0
+  }
+}
+
+fn assignment(lx:&mut Lexer) -> Result<Assign,ErrorKind> {
+  let nm = Node::new(lx.slice().to_string(),lx.span());
   
-  lx.expect(Token::Eq).map_err(|_|"we need an '=' to assign a name")?;
+  lx.expect(Token::Eq).map_err(|_|Unexpected("we need an '=' to assign a name"))?;
 
-  let value = val(lx,env)?;
-  let nm = &lx.src()[nm_span];
-  env.set(nm,value);
-  Ok(())
+  let value = val(lx)?;
+  Ok((nm,value))
 }
 
-fn slot_assignment<RT:Runtime>(lx:&mut Lexer, env:&mut Env<RT>) -> Result<(),&'static str> {
+fn slot_assignment(lx:&mut Lexer) -> Result<Stmt,ErrorKind> {
   lx.advance();
 
-  lx.expect(Token::Digits).map_err(|_|"we need a number for this slot")?;
-  let snum = lx.slice().parse::<usize>().map_err(|_|"coldn't parse this slot number")?;
+  if lx.lookahead() == Token::CloseAng {
+    lx.advance();
+    return Err(Unexpected("we need a number for this slot"));
+  }
+  let snum = val(lx)?;
 
-  lx.expect(Token::CloseAng).map_err(|_|"we need a closing angle for this slot number")?;
-  lx.expect(Token::Eq).map_err(|_|"we are looking for an equals sign to assign a slot")?;
+  lx.expect(Token::CloseAng).map_err(|_|Unexpected("we need a closing angle for this slot number"))?;
+  lx.expect(Token::Eq).map_err(|_|Unexpected("we are looking for an equals sign to assign a slot"))?;
   
-  let (len,evs) = match val(lx,env) {
-    Ok(Val::Bar(len,evs)) => (len,evs),
-    Ok(_) => return Err("you can only assign bars or sequences to a slot"),
-    Err(e) => return Err(e)
-  };
+  Ok(Stmt::SlotAssign(snum,val(lx)?))
+}
+
+//a value, or a range of them like 0..7
+pub fn val(lx:&mut Lexer) -> Result<Node<Expr>,ErrorKind> {
+  let start = lx.lookahead_span().start;
+  let v = sum(lx)?;
+  if lx.lookahead() != Token::DotDot {
+    return Ok(v);
+  }
 
-  env.assign_slot(snum,len,evs);
-  Ok(())
+  lx.advance();
+  let end = sum(lx)?;
+  Ok(Node::new(Expr::Range(Box::new(v),Box::new(end)),start..lx.span_end()))
 }
 
-fn val<RT:Runtime>(lx:&mut Lexer,env:&mut Env<RT>) -> Result<Val,&'static str> {
-  match lx.lookahead() {
-    Token::OpenSq => bar(lx,env),
-    Token::OpenCrl => sequence(lx,env),
-    Token::OpenParen => trigger(lx),
-    Token::Sym | Token::Digits => alias(lx,env),
-    Token::Eof => Err("woah, the input ran out"),
-    Token::Err => Err("we couldn't figure this symbol out"),
-    _ => Err("we are looking for a value, like a trigger, a bar or a sequence"),
+//a value with maybe some maths on it, * and / go before + and -
+fn sum(lx:&mut Lexer) -> Result<Node<Expr>,ErrorKind> {
+  let start = lx.lookahead_span().start;
+  let mut v = term(lx)?;
+  loop {
+    let op = match lx.lookahead() {
+      Token::Plus => Op::Add,
+      Token::Dash => Op::Sub,
+      _ => return Ok(v)
+    };
+    lx.advance();
+
+    let rhs = term(lx)?;
+    v = Node::new(Expr::Bin(op,Box::new(v),Box::new(rhs)),start..lx.span_end());
   }
 }
 
-fn bar<RT:Runtime>(lx:&mut Lexer,env:&mut Env<RT>) -> Result<Val,&'static str> {
-  let mut lb = LineBuilder::default();
-  bar::parse(lx,env,&mut lb)?;
-  let (len,evs) = lb.done();
-  Ok(Val::Bar(len,evs))
+//a * after a bar or a trigger repeats it, so "[X -]*3" is still three times through
+fn term(lx:&mut Lexer) -> Result<Node<Expr>,ErrorKind> {
+  let start = lx.lookahead_span().start;
+  let mut v = atom(lx)?;
+  loop {
+    let op = match lx.lookahead() {
+      Token::Star => Op::Mul,
+      Token::Slash => Op::Div,
+      _ => return Ok(v)
+    };
+    lx.advance();
+
+    let rhs = atom(lx)?;
+    v = Node::new(Expr::Bin(op,Box::new(v),Box::new(rhs)),start..lx.span_end());
+  }
 }
 
-fn sequence<RT:Runtime>(lx:&mut Lexer,env:&mut Env<RT>) -> Result<Val,&'static str> {
+fn atom(lx:&mut Lexer) -> Result<Node<Expr>,ErrorKind> {
+  let start = lx.lookahead_span().start;
+  let v = match lx.lookahead() {
+    Token::OpenSq => bar::parse(lx).map(Expr::Bar),
+    Token::OpenCrl => sequence(lx),
+    Token::OpenParen => trigger(lx).map(Expr::Trigger),
+    Token::Sym => alias(lx),
+    Token::Digits | Token::Decimal => {
+      lx.advance();
+      lx.slice().parse::<f64>().map(Expr::Num).map_err(|_|OutOfRange("couldn't parse this number"))
+    },
+    Token::Str => {
+      lx.advance();
+      let txt = lx.slice();
+      Ok(Expr::Str(txt[1..txt.len() - 1].to_string()))
+    },
+    Token::Dash => {
+      lx.advance();
+      atom(lx).map(|v|Expr::Neg(Box::new(v)))
+    },
+    Token::Plus => {
+      lx.advance();
+      atom(lx).map(|v|v.item)
+    },
+    Token::Eof => Err(InputEnded),
+    Token::Err => Err(BadSymbol),
+    t if t.is_keyword() => {
+      lx.advance();
+      Err(keyword(lx))
+    },
+    _ => Err(Unexpected("we are looking for a value, like a trigger, a bar, a sequence or a number")),
+  }?;
+
+  Ok(Node::new(v,start..lx.span_end()))
+}
+
+//for i in 0..7 { ... }, the body is statements just like the top of a file
+fn for_loop(lx:&mut Lexer) -> Result<Stmt,ErrorKind> {
   lx.advance();
-  let mut lb = LineBuilder::default();
-  env.step_in();
-  seq_assignments(lx,env)?;
-  seq_bars(lx,env,&mut lb)?;
-  
-  lx.expect(Token::CloseCrl).map_err(|_|"we need a '}' to finish the sequence")?;
 
-  let (len,evs) = lb.done();
-  Ok(Val::Bar(len,evs))
+  let nm = match lx.next() {
+    Token::Sym => Node::new(lx.slice().to_string(),lx.span()),
+    Token::Err => return Err(BadSymbol),
+    Token::Eof => return Err(InputEnded),
+    t if t.is_keyword() => return Err(keyword(lx)),
+    _ => return Err(Unexpected("we need a name to count with, like 'for i in 0..7'"))
+  };
+
+  lx.expect(Token::In).map_err(|_|Unexpected("we need an 'in' after the name"))?;
+  let over = val(lx)?;
+  lx.expect(Token::OpenCrl).map_err(|_|Unexpected("we need a '{' to start the loop"))?;
+
+  let mut body = vec![];
+  loop {
+    match lx.lookahead() {
+      Token::Eol | Token::Semi => lx.advance(),
+      Token::CloseCrl => {
+        lx.advance();
+        return Ok(Stmt::For(nm,over,body));
+      },
+      Token::Eof => return Err(InputEnded),
+      _ => {
+        let start = lx.lookahead_span().start;
+        let stmt = statement(lx)?;
+        body.push(Node::new(stmt,start..lx.span_end()));
+      }
+    }
+  }
+}
+
+fn sequence(lx:&mut Lexer) -> Result<Expr,ErrorKind> {
+  lx.advance();
+  let assigns = seq_assignments(lx)?;
+  let bars = seq_bars(lx)?;
+  
+  lx.expect(Token::CloseCrl).map_err(|_|Unexpected("we need a '}' to finish the sequence"))?;
+  Ok(Expr::Seq(assigns,bars))
 }
 
-fn seq_assignments<RT:Runtime>(lx:&mut Lexer,env:&mut Env<RT>) -> Result<(),&'static str> {
+fn seq_assignments(lx:&mut Lexer) -> Result<Vec<Assign>,ErrorKind> {
+  let mut assigns = vec![];
   loop {
     match lx.lookahead() {
       Token::Eol => lx.advance(),
       Token::Sym | Token::Digits => {
         lx.advance();
-        assignment(lx,env)?;
+        assigns.push(assignment(lx)?);
       }
-      Token::OpenSq => return Ok(()),
-      Token::Err => return Err("we couldn't figure this symbol out"),
-      Token::Eof => return Err("woah, the input ran out"),
-      _ => return Err("we are looking for a value, like a trigger or bar, or another sequence")
+      Token::OpenSq => return Ok(assigns),
+      Token::Err => return Err(BadSymbol),
+      Token::Eof => return Err(InputEnded),
+      t if t.is_keyword() => {
+        lx.advance();
+        return Err(keyword(lx));
+      },
+      _ => return Err(Unexpected("we are looking for a value, like a trigger or bar, or another sequence"))
     }
   }
 }
 
-fn seq_bars<RT>(lx:&mut Lexer,env:&mut Env<RT>,lb:&mut LineBuilder) -> Result<(),&'static str> 
-where
-  RT:Runtime
-{
+fn seq_bars(lx:&mut Lexer) -> Result<Vec<Node<Steps>>,ErrorKind> {
+  let mut bars = vec![];
   loop {
     match lx.lookahead() {
       Token::Eol => lx.advance(),
       Token::OpenSq => {
-        bar::parse(lx,env,lb)?;
-        lb.cr();
+        let start = lx.lookahead_span().start;
+        let steps = bar::parse(lx)?;
+        bars.push(Node::new(steps,start..lx.span_end()));
       }
-      Token::CloseCrl => return Ok(()),
-      Token::Err => return Err("we couldn't figure this symbol out"),
-      Token::Eof => return Err("woah, teh input ran out"),
-      _ => return Err("we are looking for some bars!")
+      Token::CloseCrl => return Ok(bars),
+      Token::Err => return Err(BadSymbol),
+      Token::Eof => return Err(InputEnded),
+      _ => return Err(Unexpected("we are looking for some bars!"))
     }
   }
 }
 
-fn trigger(lx:&mut Lexer) -> Result<Val,&'static str> {
-  lx.expect(Token::OpenParen).map_err(|_|"somehow we lost the '('")?;
+fn trigger(lx:&mut Lexer) -> Result<Trigger,ErrorKind> {
+  lx.expect(Token::OpenParen).map_err(|_|Unexpected("somehow we lost the '('"))?;
+
+  //a + between pitches plays them all at once
+  let mut pitches = pitch(lx)?;
+  loop {
+    match lx.lookahead() {
+      Token::Plus => lx.advance(),
+      _ if degree_joins(lx) => (),
+      _ => break
+    }
+    pitches.extend(pitch(lx)?);
+  }
 
-  let nn = match lx.next() {
-    Token::Note => Ok(note_to_u8(lx.slice())),
+  let vel = match lx.next() {
     Token::Digits => match lx.slice().parse::<u8>() {
       Ok(n) if n < 128 => Ok(n),
-      Ok(_) => Err("this note number is out of range, should be 0-127"),
-      Err(_) => Err("couldn't parse this note number")
+      Ok(_) => Err(OutOfRange("this velocity is out of range, should be 0-127")),
+      Err(_) => Err(OutOfRange("coudln't parse this velocity"))
     },
-    Token::Err => Err("we couldn't figure this symbol out"),
-    Token::Eof => Err("woah, the input ran out"),
-    _ => Err("we need some numbers or a note like C#4 or something")
+    Token::Err => Err(BadSymbol),
+    Token::Eof => Err(InputEnded),
+    _ => Err(Unexpected("we are looking for a trigger velocity, like a number between 0-127"))
   }?;
 
-  let vel = match lx.next() {
+  let prob = match lx.lookahead() {
+    Token::Quest => chance(lx)?,
+    _ => 100
+  };
+
+  lx.expect(Token::CloseParen).map_err(|_|Unexpected("somehow missed the ')' in a trigger"))?;
+
+  Ok(Trigger{pitches,vel,prob})
+}
+
+//one note, a set of notes to pick from or a named chord
+fn pitch(lx:&mut Lexer) -> Result<Vec<Pitch>,ErrorKind> {
+  if lx.lookahead() == Token::Chord {
+    lx.advance();
+    return chord_to_u8s(lx.slice()).map(|nns|nns.into_iter().map(Pitch::Note).collect());
+  }
+
+  let first = tone(lx)?;
+
+  //a | between notes picks one at random, a , plays them in turn
+  let pitch = match lx.lookahead() {
+    sep@(Token::Pipe | Token::Comma) => {
+      let mut ps = vec![first];
+      while lx.lookahead() == sep {
+        lx.advance();
+        ps.push(tone(lx)?);
+      }
+
+      match lx.lookahead() {
+        Token::Pipe | Token::Comma => return Err(Unexpected("you can't mix '|' and ',' in one set of notes")),
+        _ if sep == Token::Pipe => Pitch::Choose(ps),
+        _ => Pitch::Cycle(ps,0)
+      }
+    },
+    _ => first
+  };
+
+  Ok(vec![pitch])
+}
+
+//"^5+^7" lexes as "^5+" then "^7", when the next pitch is right up
+//against a degree that ends in + the + means "and" not "octave up"
+fn degree_joins(lx:&Lexer) -> bool {
+  let joinable = matches!(lx.lookahead(),Token::Note | Token::Chord | Token::Degree | Token::Digits);
+  lx.slice().starts_with('^') && lx.slice().ends_with('+') && joinable
+  && lx.span_end() == lx.lookahead_span().start
+}
+
+//a single note name, note number or scale degree
+fn tone(lx:&mut Lexer) -> Result<Pitch,ErrorKind> {
+  match lx.next() {
+    Token::Note => note_to_u8(lx.slice()).map(Pitch::Note),
+    Token::Degree => {
+      let txt = lx.slice();
+      let digits = txt[1..].trim_end_matches(['+','-']);
+      let deg = digits.parse::<usize>().map_err(|_|OutOfRange("couldn't parse this scale degree"))?;
+      let ups = txt.matches('+').count() as isize - degree_joins(lx) as isize;
+      let downs = txt.matches('-').count() as isize;
+      Ok(Pitch::Degree{deg:deg - 1,oct:ups - downs,shift:0})
+    },
     Token::Digits => match lx.slice().parse::<u8>() {
-      Ok(n) if n < 128 => Ok(n),
-      Ok(_) => Err("this velocity is out of range, should be 0-127"),
-      Err(_) => Err("coudln't parse this velocity")
+      Ok(n) if n < 128 => Ok(Pitch::Note(n)),
+      Ok(_) => Err(OutOfRange("this note number is out of range, should be 0-127")),
+      Err(_) => Err(OutOfRange("couldn't parse this note number"))
     },
-    Token::Err => Err("we couldn't figure this symbol out"),
-    Token::Eof => Err("couldn't parse this velocity"),
-    _ => Err("we are looking for a trigger velocity, like a number between 0-127")
-  }?;
+    Token::Err => Err(BadSymbol),
+    Token::Eof => Err(InputEnded),
+    _ => Err(Unexpected("we need some numbers or a note like C#4 or something"))
+  }
+}
+
+//an optional "*4" after a step in a bar, no star means it's there once
+pub fn repeats(lx:&mut Lexer) -> Result<usize,ErrorKind> {
+  if lx.lookahead() != Token::Star {
+    return Ok(1);
+  }
+
+  lx.advance();
+  lx.expect(Token::Digits).map_err(|_|Unexpected("we need a number of times to repeat after the '*'"))?;
+  match lx.slice().parse::<usize>() {
+    Ok(0) => Err(OutOfRange("you can't repeat something zero times")),
+    Ok(n) => Ok(n),
+    Err(_) => Err(OutOfRange("couldn't parse this number of repeats"))
+  }
+}
 
-  lx.expect(Token::CloseParen).map_err(|_|"somehow missed the ')' in a trigger")?;
+//parses a "?40" style chance, the number is a percentage
+pub fn chance(lx:&mut Lexer) -> Result<u8,ErrorKind> {
+  lx.expect(Token::Quest).map_err(|_|Unexpected("somehow missed the '?'"))?;
 
-  Ok(Val::Trigger(Trigger{nn,vel}))
+  match lx.next() {
+    Token::Digits => match lx.slice().parse::<u8>() {
+      Ok(n) if n <= 100 => Ok(n),
+      Ok(_) => Err(OutOfRange("this chance is out of range, should be 0-100")),
+      Err(_) => Err(OutOfRange("couldn't parse this chance"))
+    },
+    Token::Err => Err(BadSymbol),
+    Token::Eof => Err(InputEnded),
+    _ => Err(Unexpected("we need a chance after the '?', like a number between 0-100"))
+  }
 }
 
-fn alias<RT:Runtime>(lx:&mut Lexer,env:&mut Env<RT>) -> Result<Val,&'static str> {
+//a name or a call to a builtin, bars use this for their steps too
+pub fn alias(lx:&mut Lexer) -> Result<Expr,ErrorKind> {
   lx.advance();
-  match env.lookup(lx.slice()) {
-    Some(v) => Ok(v.clone()),
-    None => Err("we couldn't find a value with this name")
+  if is_call(lx) {
+    return call(lx);
+  }
+
+  Ok(Expr::Name(lx.slice().to_string()))
+}
+
+//a call needs the '(' right up against the name, that way
+//"X (3: X X X)" in a bar is a trigger and then a group
+fn is_call(lx:&Lexer) -> bool {
+  lx.lookahead() == Token::OpenParen && lx.span_end() == lx.lookahead_span().start
+}
+
+//builtins look like name(arg,arg,...), the name has already
+//been eaten by the time we get here so lx.slice() is the name
+fn call(lx:&mut Lexer) -> Result<Expr,ErrorKind> {
+  let nm = Node::new(lx.slice().to_string(),lx.span());
+  lx.expect(Token::OpenParen).map_err(|_|Unexpected("we need a '(' to call a function"))?;
+
+  let mut args = vec![];
+  if lx.lookahead() == Token::CloseParen {
+    lx.advance();
+    return Ok(Expr::Call(nm,args));
+  }
+
+  loop {
+    args.push(val(lx)?);
+    match lx.next() {
+      Token::Comma => (),
+      Token::CloseParen => return Ok(Expr::Call(nm,args)),
+      Token::Err => return Err(BadSymbol),
+      Token::Eof => return Err(InputEnded),
+      _ => return Err(Unexpected("we need a ',' between arguments or a ')' to finish the function call"))
+    }
   }
 }
 
-fn note_to_u8(txt:&str) -> u8 {
+pub fn pitch_class(txt:&str) -> isize {
   let pc : isize = match &txt[0..1] {
     "c"|"C" => 0,
     "d"|"D" => 2,   
@@ -174,23 +491,294 @@ fn note_to_u8(txt:&str) -> u8 {
     "f"|"F" => 5, 
     "g"|"G" => 7,
     "a"|"A" => 9,
-    "b"|"B" => 1,
+    "b"|"B" => 11,
     _ => unreachable!("shouldn't have invalid notes")
   };
 
-  let modifier : isize = match &txt[1..2] {
-    "b" => -1,
-    "_" => 0,
-    "#" => 1,
-    _ => unreachable!("shouldn't have invalid notes")
+  let modifier : isize = match txt.get(1..2) {
+    Some("b") => -1,
+    Some("#") => 1,
+    _ => 0
   };
 
+  pc + modifier
+}
+
+pub fn midi_note(pc:isize,octave:isize) -> Result<u8,ErrorKind> {
+  match 24 + pc + (12*octave) {
+    n @ 0..=127 => Ok(n as u8),
+    _ => Err(OutOfRange("this note is out of range, it needs to be a midi note 0-127"))
+  }
+}
+
+pub fn note_to_u8(txt:&str) -> Result<u8,ErrorKind> {
   let octave = match &txt[2..3].parse::<isize>() {
     Ok(i) => *i,
     Err(_) => unreachable!("shouldn't have invalid notes here")
   };
 
-  let val = 24 + pc + (12*octave) + modifier;
-  val as u8
+  midi_note(pitch_class(txt),octave)
+}
+
+//chords look like Cmaj7@3, the root then the quality then the octave
+fn chord_to_u8s(txt:&str) -> Result<Vec<u8>,ErrorKind> {
+  let (name,octave) = txt.split_once('@').expect("chords always have an @");
+  let octave = octave.parse::<isize>().expect("chord octaves are always digits");
+
+  let quality = match name.get(1..2) {
+    Some("#") | Some("b") if name.len() > 2 => &name[2..],
+    _ => &name[1..]
+  };
+
+  let intervals : &[isize] = match quality {
+    "maj" => &[0,4,7],
+    "min" => &[0,3,7],
+    "7" => &[0,4,7,10],
+    "maj7" => &[0,4,7,11],
+    "m7" => &[0,3,7,10],
+    "dim" => &[0,3,6],
+    "sus2" => &[0,2,7],
+    "sus4" => &[0,5,7],
+    _ => unreachable!("the lexer only makes chords it knows")
+  };
+
+  let root = pitch_class(name);
+  intervals.iter().map(|i|midi_note(root + i,octave)).collect()
 }
 
+#[cfg(test)]
+mod tests {
+  use crate::{Env,test_utils};
+  use crate::seq_types::Val;
+  use crate::ast::Step;
+  use super::*;
+
+  fn trig(input:&str) -> Result<Trigger,ErrorKind> {
+    let mut lx = Lexer::new(input);
+    trigger(&mut lx)
+  }
+
+  #[test]
+  fn test_trigger() {
+    let t = trig("(C_1 127)").expect("shouldn't error");
+    assert_eq!(t.pitches,[Pitch::Note(36)]);
+    assert_eq!(t.vel,127);
+    assert_eq!(t.prob,100);
+
+    let t = trig("(C_1 127 ?40)").expect("shouldn't error");
+    assert_eq!(t.prob,40);
+
+    let t = trig("(C_1|D#1|G_1 100)").expect("shouldn't error");
+    assert_eq!(t.pitches,[Pitch::Choose(vec![Pitch::Note(36),Pitch::Note(39),Pitch::Note(43)])]);
+
+    let t = trig("(C_1,60 100)").expect("shouldn't error");
+    assert_eq!(t.pitches,[Pitch::Cycle(vec![Pitch::Note(36),Pitch::Note(60)],0)]);
+
+    assert!(trig("(C_1|D#1,G_1 100)").is_err());
+    assert!(trig("(C_1| 100)").is_err());
+    assert!(trig("(C_1 100 ?101)").is_err());
+  }
+
+  #[test]
+  fn test_chords() {
+    let t = trig("(C_3+E_3+G_3 100)").expect("shouldn't error");
+    assert_eq!(t.pitches,[Pitch::Note(60),Pitch::Note(64),Pitch::Note(67)]);
+
+    let t = trig("(Cmaj7@3 100)").expect("shouldn't error");
+    assert_eq!(t.pitches,[Pitch::Note(60),Pitch::Note(64),Pitch::Note(67),Pitch::Note(71)]);
+
+    let t = trig("(F#min@2 100)").expect("shouldn't error");
+    assert_eq!(t.pitches,[Pitch::Note(54),Pitch::Note(57),Pitch::Note(61)]);
+
+    let t = trig("(Ebsus4@1+C_1|D_1 100)").expect("shouldn't error");
+    assert_eq!(t.pitches,[Pitch::Note(39),Pitch::Note(44),Pitch::Note(46),Pitch::Choose(vec![Pitch::Note(36),Pitch::Note(38)])]);
+
+    //B is the one below the next C, not the one above this C
+    let t = trig("(B_1 100)").expect("shouldn't error");
+    assert_eq!(t.pitches,[Pitch::Note(47)]);
+
+    let t = trig("(Bbsus4@1 100)").expect("shouldn't error");
+    assert_eq!(t.pitches,[Pitch::Note(46),Pitch::Note(51),Pitch::Note(53)]);
+
+    assert!(trig("(G_8+ 100)").is_err());
+    assert!(trig("(G#8 100)").is_err());
+    assert!(trig("(Gmaj@8 100)").is_err());
+  }
+
+  #[test]
+  fn test_degrees() {
+    let deg = |deg,oct| Pitch::Degree{deg,oct,shift:0};
+
+    let t = trig("(^1 100)").expect("shouldn't error");
+    assert_eq!(t.pitches,[deg(0,0)]);
+
+    let t = trig("(^5+ 90)").expect("shouldn't error");
+    assert_eq!(t.pitches,[deg(4,1)]);
+
+    let t = trig("(^3-- 90)").expect("shouldn't error");
+    assert_eq!(t.pitches,[deg(2,-2)]);
+
+    let t = trig("(^1+^3+^5++ 90)").expect("shouldn't error");
+    assert_eq!(t.pitches,[deg(0,0),deg(2,0),deg(4,2)]);
+
+    let t = trig("(^1 + ^5+ + C_3 90)").expect("shouldn't error");
+    assert_eq!(t.pitches,[deg(0,0),deg(4,1),Pitch::Note(60)]);
+
+    let t = trig("(^1|^8 90)").expect("shouldn't error");
+    assert_eq!(t.pitches,[Pitch::Choose(vec![deg(0,0),deg(7,0)])]);
+
+    assert!(trig("(^0 90)").is_err());
+  }
+
+  #[test]
+  fn test_repeats() {
+    let mut env = Env::new(crate::NullRuntime);
+    env.set("X",Val::Trigger(Trigger{pitches:vec![Pitch::Note(1)],vel:1,prob:100}));
+    match test_utils::eval_val(&mut env,"[X -]*3").expect("shouldn't error") {
+      Val::Bar(len,evs) => {
+        assert_eq!(len,6);
+        assert_eq!(evs.len(),3);
+      },
+      _ => panic!("should be a bar")
+    }
+
+    match test_utils::eval_val(&mut env,"(C_1 100)*4").expect("shouldn't error") {
+      Val::Bar(len,evs) => {
+        assert_eq!(len,4);
+        assert_eq!(evs.len(),4);
+      },
+      _ => panic!("should be a bar")
+    }
+
+    assert!(test_utils::eval_val(&mut env,"[X]*").is_err());
+  }
+
+  #[test]
+  fn test_tree() {
+    let src = "X = (C_1 100)\n<0> = [X - euc(3,8,X)]*2\nplay 0";
+    let mut lx = Lexer::new(src);
+    let (tree,errs) = root(&mut lx);
+    assert!(errs.is_empty());
+    assert_eq!(tree.len(),3);
+    assert_eq!(&src[tree[0].span.clone()],"X = (C_1 100)");
+    assert_eq!(&src[tree[2].span.clone()],"play 0");
+
+    let Stmt::SlotAssign(snum,v) = &tree[1].item else {
+      panic!("should be a slot assignment");
+    };
+    assert_eq!(snum.item,Expr::Num(0.0));
+    assert_eq!(&src[v.span.clone()],"[X - euc(3,8,X)]*2");
+
+    let Expr::Bin(Op::Mul,b,n) = &v.item else {
+      panic!("should be repeated");
+    };
+    assert_eq!(n.item,Expr::Num(2.0));
+    let Expr::Bar(steps) = &b.item else {
+      panic!("should be a bar");
+    };
+    assert_eq!(steps.len(),3);
+    assert_eq!(&src[steps[2].span.clone()],"euc(3,8,X)");
+    assert!(matches!(&steps[1].item,Step::Rest(1)));
+
+    let Step::Hit{target,..} = &steps[2].item else {
+      panic!("should be a hit");
+    };
+    let Expr::Call(nm,args) = &target.item else {
+      panic!("should be a call");
+    };
+    assert_eq!(nm.item,"euc");
+    assert_eq!(args[1],Node::new(Expr::Num(8.0),31..32));
+
+    //names don't get looked up until it runs
+    let mut lx = Lexer::new("y = [nothing_here]");
+    assert!(root(&mut lx).1.is_empty());
+
+    let mut lx = Lexer::new("play");
+    assert_eq!(root(&mut lx).1.len(),1);
+  }
+
+  #[test]
+  fn test_recovery() {
+    let src = "X = (C_1 100)\nplay\nY = [X @]\nbpm 120\nZ = {\n  q = (C_1 300)\n  [q -]\n}\nstop 0\n} ]\nsync 0";
+    let mut lx = Lexer::new(src);
+    let (tree,errs) = root(&mut lx);
+
+    assert_eq!(tree.len(),3);
+    assert!(matches!(&tree[0].item,Stmt::Assign(nm,_) if nm.item == "X"));
+    assert!(matches!(&tree[1].item,Stmt::Bpm(n,None) if n.item == Expr::Num(120.0)));
+    assert!(matches!(&tree[2].item,Stmt::Stop(t) if t.item.exprs()[0].item == Expr::Num(0.0)));
+
+    //the bad character isn't eaten so the error sits on the X before it
+    let spots : Vec<_> = errs.iter().map(|e|&src[e.span.clone()]).collect();
+    assert_eq!(spots,["\n","X","300","}",""]);
+    assert!(matches!(errs[2].item,OutOfRange(_)));
+    assert_eq!(errs[4].item,Unexpected("we need a sync division"));
+
+    let mut lx = Lexer::new("key D dorain");
+    let (_,errs) = root(&mut lx);
+    assert_eq!(errs[0].item,ErrorKind::UnknownScale{suggestions:vec!["dorian".into()]});
+
+    let (tree,errs) = root(&mut Lexer::new("clock_out 1 ON\nclock_out 0 maybe"));
+    assert!(matches!(&tree[0].item,Stmt::ClockOut(_,on) if on.item));
+    assert_eq!(errs[0].item,Unexpected("we need 'on' or 'off' for the clock"));
+
+    let (tree,errs) = root(&mut Lexer::new("clock_in 1 + 1\nclock_in OFF\nclock_in"));
+    assert!(matches!(&tree[0].item,Stmt::ClockIn(Some(_))));
+    assert!(matches!(&tree[1].item,Stmt::ClockIn(None)));
+    assert_eq!(errs.len(),1);
+
+    let (tree,errs) = root(&mut Lexer::new("CLOCK_STATS"));
+    assert!(matches!(&tree[0].item,Stmt::ClockStats));
+    assert!(errs.is_empty());
+
+    let (tree,errs) = root(&mut Lexer::new("bpm 92.5\nbpm 140 over 4 * 4\ntap\nbpm 140 over"));
+    assert!(matches!(&tree[0].item,Stmt::Bpm(n,None) if n.item == Expr::Num(92.5)));
+    assert!(matches!(&tree[1].item,Stmt::Bpm(_,Some(bars)) if matches!(bars.item,Expr::Bin(..))));
+    assert!(matches!(&tree[2].item,Stmt::Tap));
+    assert_eq!(errs[0].item,Unexpected("we need how many bars to get there in, like bpm 140 over 16"));
+
+    let (tree,errs) = root(&mut Lexer::new("pause\nContinue\nrestart\nlocate 4 * 2\nlocate"));
+    assert!(matches!(tree[..3],[Node{item:Stmt::Pause,..},Node{item:Stmt::Continue,..},Node{item:Stmt::Restart,..}]));
+    assert!(matches!(&tree[3].item,Stmt::Locate(bar) if matches!(bar.item,Expr::Bin(..))));
+    assert_eq!(errs[0].item,Unexpected("we need a bar to go to, like locate 8"));
+  }
+
+  #[test]
+  fn test_keywords() {
+    //every spot a name goes says which keyword got in the way
+    let src = "tap = 3
+X = [Pause -]
+n = slot + 1
+for in in 0..2 { }
+def over(a) = a
+def f(all) = all
+slot key = 0
+S = {
+  locate = 2
+  [S]
+}";
+    let (tree,errs) = root(&mut Lexer::new(src));
+    assert!(tree.is_empty());
+
+    let found : Vec<_> = errs.iter().map(|e|(e.item.clone(),&src[e.span.clone()])).collect();
+    assert_eq!(found,[
+      (ErrorKind::Keyword("tap"),"tap"),
+      (ErrorKind::Keyword("pause"),"Pause"),
+      (ErrorKind::Keyword("slot"),"slot"),
+      (ErrorKind::Keyword("in"),"in"),
+      (ErrorKind::Keyword("over"),"over"),
+      (ErrorKind::Keyword("all"),"all"),
+      (ErrorKind::Keyword("key"),"key"),
+      (ErrorKind::Keyword("locate"),"locate")
+    ]);
+    assert_eq!(errs[0].item.msg(),"keywords can't be used as names");
+    assert_eq!(errs[0].item.help().unwrap(),"`tap` is a keyword, a name like `my_tap` works instead");
+
+    //it's only a keyword when it's the whole name
+    let (tree,errs) = root(&mut Lexer::new("tapped = 3
+X = [my_pause -]
+tap"));
+    assert_eq!(tree.len(),3);
+    assert!(errs.is_empty());
+  }
+}
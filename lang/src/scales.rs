@@ -0,0 +1,110 @@
+//a key is a root note and the semitones of each degree above it,
+//the runtime hangs on to it so scale degrees get worked out at play time
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+pub struct Key {
+  pub root:u8,
+  pub steps:&'static [u8]
+}
+
+impl Default for Key {
+  fn default() -> Self {
+    Self {
+      root:60,
+      steps:IONIAN
+    }
+  }
+}
+
+impl Key {
+  //deg counts from 0, oct moves whole octaves of the scale
+  //and shift is extra semitones from transposing
+  pub fn resolve(&self,deg:usize,oct:isize,shift:isize) -> Option<u8> {
+    let len = self.steps.len();
+    let oct = oct + (deg / len) as isize;
+    let nn = self.root as isize + self.steps[deg % len] as isize + (12 * oct) + shift;
+
+    match nn {
+      0..=127 => Some(nn as u8),
+      _ => None
+    }
+  }
+}
+
+//written the way the key statement takes it, like "D_3 dorian"
+impl std::fmt::Display for Key {
+  fn fmt(&self,f:&mut std::fmt::Formatter) -> std::fmt::Result {
+    const PCS : [&str;12] = ["C_","C#","D_","D#","E_","F_","F#","G_","G#","A_","A#","B_"];
+    match self.root {
+      r @ 24.. => write!(f,"{}{}",PCS[(r % 12) as usize],(r - 24) / 12)?,
+      r => write!(f,"{}",r)?
+    }
+
+    match NAMES.iter().find(|nm|scale(nm) == Some(self.steps)) {
+      Some(nm) => write!(f," {}",nm),
+      None => write!(f," {:?}",self.steps)
+    }
+  }
+}
+
+const IONIAN : &[u8] = &[0,2,4,5,7,9,11];
+const DORIAN : &[u8] = &[0,2,3,5,7,9,10];
+const PHRYGIAN : &[u8] = &[0,1,3,5,7,8,10];
+const LYDIAN : &[u8] = &[0,2,4,6,7,9,11];
+const MIXOLYDIAN : &[u8] = &[0,2,4,5,7,9,10];
+const AEOLIAN : &[u8] = &[0,2,3,5,7,8,10];
+const LOCRIAN : &[u8] = &[0,1,3,5,6,8,10];
+const HARMONIC_MINOR : &[u8] = &[0,2,3,5,7,8,11];
+const MELODIC_MINOR : &[u8] = &[0,2,3,5,7,9,11];
+const MAJOR_PENTATONIC : &[u8] = &[0,2,4,7,9];
+const MINOR_PENTATONIC : &[u8] = &[0,3,5,7,10];
+const BLUES : &[u8] = &[0,3,5,6,7,10];
+const CHROMATIC : &[u8] = &[0,1,2,3,4,5,6,7,8,9,10,11];
+
+//every name scale() knows, for suggesting when one is misspelled
+const NAMES : &[&str] = &[
+  "major","ionian","dorian","phrygian","lydian","mixolydian","minor","aeolian","locrian",
+  "harmonic_minor","melodic_minor","pentatonic","major_pentatonic","minor_pentatonic","blues","chromatic"
+];
+
+pub fn similar(name:&str) -> Vec<String> {
+  crate::err::suggest(&name.to_lowercase(),NAMES.iter().copied())
+}
+
+pub fn scale(name:&str) -> Option<&'static [u8]> {
+  match name.to_lowercase().as_ref() {
+    "major" | "ionian" => Some(IONIAN),
+    "dorian" => Some(DORIAN),
+    "phrygian" => Some(PHRYGIAN),
+    "lydian" => Some(LYDIAN),
+    "mixolydian" => Some(MIXOLYDIAN),
+    "minor" | "aeolian" => Some(AEOLIAN),
+    "locrian" => Some(LOCRIAN),
+    "harmonic_minor" => Some(HARMONIC_MINOR),
+    "melodic_minor" => Some(MELODIC_MINOR),
+    "pentatonic" | "major_pentatonic" => Some(MAJOR_PENTATONIC),
+    "minor_pentatonic" => Some(MINOR_PENTATONIC),
+    "blues" => Some(BLUES),
+    "chromatic" => Some(CHROMATIC),
+    _ => None
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_resolve() {
+    let key = Key{root:62,steps:scale("dorian").unwrap()};
+    assert_eq!(key.resolve(0,0,0),Some(62));
+    assert_eq!(key.resolve(2,0,0),Some(65));
+    assert_eq!(key.resolve(7,0,0),Some(74));
+    assert_eq!(key.resolve(4,1,0),Some(81));
+    assert_eq!(key.resolve(0,-1,-2),Some(48));
+    assert_eq!(key.resolve(0,6,0),None);
+    assert_eq!(key.resolve(0,-6,0),None);
+    assert_eq!(key.to_string(),"D_3 dorian");
+    assert!(scale("Minor").is_some());
+    assert!(scale("wonky").is_none());
+  }
+}
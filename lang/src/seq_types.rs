@@ -1,17 +1,81 @@
 use std::collections::HashMap;
 
-#[derive(Debug,Copy,Clone)]
+//the choice sets keep their picking up to the player,
+//Cycle carries the index of the next pitch it will play.
+//Degrees count from 0 and get turned into notes by the player
+//against whatever key is set at the time
+#[derive(Clone,PartialEq,Eq)]
+pub enum Pitch {
+  Note(u8),
+  Degree{deg:usize,oct:isize,shift:isize},
+  Choose(Vec<Pitch>),
+  Cycle(Vec<Pitch>,usize)
+}
+
+impl Pitch {
+  //None if any of the notes would land outside of 0-127,
+  //degrees can't be checked until they're played
+  pub fn transpose(&self,by:isize) -> Option<Pitch> {
+    match self {
+      Pitch::Note(nn) => match *nn as isize + by {
+        n @ 0..=127 => Some(Pitch::Note(n as u8)),
+        _ => None
+      },
+      Pitch::Degree{deg,oct,shift} => Some(Pitch::Degree{deg:*deg,oct:*oct,shift:shift + by}),
+      Pitch::Choose(ps) => ps.iter().map(|p|p.transpose(by)).collect::<Option<_>>().map(Pitch::Choose),
+      Pitch::Cycle(ps,next) => ps.iter().map(|p|p.transpose(by)).collect::<Option<_>>().map(|v|Pitch::Cycle(v,*next))
+    }
+  }
+}
+
+//for tests and logs, it isn't sgv: a degree that's been through trans
+//shows its semitones like ^1[+7] and there's no way to write that
+impl std::fmt::Debug for Pitch {
+  fn fmt(&self, f:&mut std::fmt::Formatter) -> Result<(),std::fmt::Error> {
+    let (ps,sep) = match self {
+      Pitch::Note(nn) => return write!(f,"{}",nn),
+      Pitch::Degree{deg,oct,shift} => {
+        write!(f,"^{}",deg + 1)?;
+        let mark = if *oct < 0 { "-" } else { "+" };
+        write!(f,"{}",mark.repeat(oct.unsigned_abs()))?;
+        if *shift != 0 {
+          write!(f,"[{:+}]",shift)?;
+        }
+        return Ok(())
+      },
+      Pitch::Choose(ps) => (ps,"|"),
+      Pitch::Cycle(ps,_) => (ps,",")
+    };
+
+    for (i,p) in ps.iter().enumerate() {
+      if i > 0 {
+        write!(f,"{}",sep)?;
+      }
+      write!(f,"{:?}",p)?;
+    }
+    Ok(())
+  }
+}
+
+//more than one pitch makes a chord
+#[derive(Debug,Clone,PartialEq)]
 pub struct Trigger {
-  pub nn:u8,
-  pub vel:u8
+  pub pitches:Vec<Pitch>,
+  pub vel:u8,
+  pub prob:u8
 }
 
-#[derive(Debug,Copy,Clone)]
+#[derive(Debug,Clone)]
 pub struct SeqNote {
-  pub nn:u8,
+  pub pitch:Pitch,
   pub vel:u8,
   pub duration:usize,
-  pub legato:bool
+  pub legato:bool,
+  //chance out of 100 that the note fires each time its step comes up
+  pub prob:u8,
+  //(position,parts) the step is cut into parts and the note starts at position,
+  //when the step is cut up the duration is counted in parts too
+  pub sub:(usize,usize)
 }
 
 #[derive(Debug,Default)]
@@ -32,9 +96,12 @@ impl LineBuilder {
     (max,self.evs)
   }
 
-  pub fn trig(&mut self, nn:u8,vel:u8,dur:usize,legato:bool) {
-    let note = SeqNote{nn,vel,duration:dur + 1,legato};
-    self.evs.entry(self.count).and_modify(|v|v.push(note)).or_insert(vec![note]);
+  //every pitch goes on the same step, that's how chords happen
+  pub fn trig(&mut self, pitches:&[Pitch],vel:u8,dur:usize,legato:bool,prob:u8) {
+    let step = self.evs.entry(self.count).or_default();
+    for pitch in pitches.iter() {
+      step.push(SeqNote{pitch:pitch.clone(),vel,duration:dur + 1,legato,prob,sub:(0,1)});
+    }
     self.count += dur + 1
   }
 
@@ -42,6 +109,21 @@ impl LineBuilder {
     self.count += len
   }
 
+  //fits all of the other line into one step of this one
+  pub fn squeeze(&mut self,other_len:usize,other_notes:&HashMap<usize,Vec<SeqNote>>) {
+    let step = self.evs.entry(self.count).or_default();
+    for i in 0..other_len {
+      for n in other_notes.get(&i).into_iter().flatten() {
+        let (pos,parts) = n.sub;
+        let mut n = n.clone();
+        n.sub = (i * parts + pos,other_len * parts);
+        step.push(n);
+      }
+    }
+
+    self.count += 1
+  }
+
   pub fn merge(&mut self,other_len:usize,other_notes:&HashMap<usize,Vec<SeqNote>>) {
     for i in 0..other_len {
 
@@ -66,34 +148,90 @@ mod tests {
   #[test]
   fn test_line_builder() {
     let mut bld = LineBuilder::default();
-    bld.trig(10,10,4,true);
+    bld.trig(&[Pitch::Note(10)],10,4,true,100);
     bld.rests(5);
-    bld.trig(11,11,4,false);
+    bld.trig(&[Pitch::Note(11)],11,4,false,40);
     bld.cr();
-    bld.trig(12,12,4,false);
+    bld.trig(&[Pitch::Choose(vec![Pitch::Note(12),Pitch::Note(13)])],12,4,false,100);
     bld.rests(15);
     let (len,map) = bld.done();
     assert_eq!(len,20);
     let trigs1 = map.get(&0).expect("should have a vec here");
     assert_eq!(trigs1.len(),2);
-    assert!(matches!(trigs1[0],SeqNote{nn:10,vel:10,duration:5,legato:true}));
-    assert!(matches!(trigs1[1],SeqNote{nn:12,vel:12,duration:5,legato:false}));
+    assert!(matches!(trigs1[0],SeqNote{pitch:Pitch::Note(10),vel:10,duration:5,legato:true,prob:100,sub:(0,1)}));
+    assert!(matches!(&trigs1[1],SeqNote{pitch:Pitch::Choose(v),vel:12,duration:5,legato:false,prob:100,sub:(0,1)} if v == &[Pitch::Note(12),Pitch::Note(13)]));
 
     let trigs2 = map.get(&10).expect("should have a vec here");
-    assert!(matches!(trigs2[0],SeqNote{nn:11,vel:11,duration:5,legato:false}));
+    assert!(matches!(trigs2[0],SeqNote{pitch:Pitch::Note(11),vel:11,duration:5,legato:false,prob:40,sub:(0,1)}));
+
+    let mut bld = LineBuilder::default();
+    bld.rests(1);
+    bld.trig(&[Pitch::Note(20),Pitch::Note(24)],20,0,false,100);
+    let (len,map) = bld.done();
+    assert_eq!(len,2);
+    let trigs3 = map.get(&1).expect("should have a vec here");
+    assert_eq!(trigs3.len(),2);
+    assert!(matches!(trigs3[1],SeqNote{pitch:Pitch::Note(24),vel:20,duration:1,legato:false,prob:100,sub:(0,1)}));
   }
 }
 
-#[derive(Debug,Clone)]
+#[derive(Clone)]
 pub enum Val {
   Bar(usize,HashMap<usize,Vec<SeqNote>>),
   Trigger(Trigger),
+  Num(f64),
+  Str(String),
+  List(Vec<Val>)
+}
+
+impl Val {
+  //the same as writing it out n times in a bar, only bars and triggers can
+  pub fn repeat(&self,n:usize) -> Option<Val> {
+    let mut lb = LineBuilder::default();
+    for _ in 0..n {
+      match self {
+        Val::Trigger(t) => lb.trig(&t.pitches,t.vel,0,false,t.prob),
+        Val::Bar(len,evs) => lb.merge(*len,evs),
+        Val::Num(_) | Val::Str(_) | Val::List(_) => return None
+      }
+    }
+
+    let (len,evs) = lb.done();
+    Some(Val::Bar(len,evs))
+  }
 }
 
-impl std::fmt::Display for Val {
+//for tests and logs, bars come out one line per chord note and a note in
+//a cut up step shows its spot like @1/3, which isn't something sgv reads
+impl std::fmt::Debug for Val {
   fn fmt(&self, f:&mut std::fmt::Formatter) -> Result<(),std::fmt::Error> {
     match self {
-      Val::Trigger(t) => write!(f,"({} {})",t.nn,t.vel),
+      Val::Num(n) => write!(f,"{}",n),
+      Val::Str(txt) => write!(f,"\"{}\"",txt),
+      Val::List(vs) => {
+        write!(f,"list(")?;
+        for (i,v) in vs.iter().enumerate() {
+          if i > 0 {
+            write!(f,",")?;
+          }
+          write!(f,"{:?}",v)?;
+        }
+        write!(f,")")
+      },
+      Val::Trigger(t) => {
+        write!(f,"(")?;
+        for (i,p) in t.pitches.iter().enumerate() {
+          if i > 0 {
+            write!(f,"+")?;
+          }
+          write!(f,"{:?}",p)?;
+        }
+
+        match t.prob {
+          100 => write!(f," {})",t.vel),
+          _ => write!(f," {} ?{})",t.vel,t.prob)
+        }
+      },
       Val::Bar(len,evs) => {
         let mut lines = 1;
         let mut ln = 0;
@@ -109,8 +247,15 @@ impl std::fmt::Display for Val {
               Some(ts) => {
                 lines = lines.max(ts.len());
                 if ln < ts.len() {
-                  let n = ts[ln];
-                  write!(f,"({} {}) ",n.nn,n.vel)?;
+                  let n = &ts[ln];
+                  write!(f,"({:?} {}",n.pitch,n.vel)?;
+                  if n.prob < 100 {
+                    write!(f," ?{}",n.prob)?;
+                  }
+                  if n.sub.1 > 1 {
+                    write!(f," @{}/{}",n.sub.0,n.sub.1)?;
+                  }
+                  write!(f,") ")?;
                   ties = n.duration - 1;
                   leg = n.legato;
                 }
@@ -0,0 +1,400 @@
+use std::path::{Path,PathBuf};
+
+use lsp_types::{
+  CompletionItem,
+  CompletionItemKind,
+  Diagnostic,
+  DiagnosticSeverity,
+  NumberOrString,
+  SemanticToken,
+  SemanticTokenModifier,
+  SemanticTokenType,
+  SemanticTokensLegend
+};
+
+use lang::{
+  ast::{self,Node,Stmt,Expr},
+  Lexer,
+  Token
+};
+
+use super::lines::Lines;
+
+//parse errors if there are any, otherwise whatever goes wrong running it.
+//a file that doesn't parse would just give a pile of unknown names.
+//the runtime doesn't do anything, running it just finds unknown names and bad values
+pub fn diagnostics(lines:&Lines,dir:Option<&Path>) -> Vec<Diagnostic> {
+  let src = lines.src();
+  let (tree,errs) = lang::parse_partial(src);
+  let errs = match errs.is_empty() {
+    true => {
+      let mut env = lang::Env::new(lang::NullRuntime);
+      if let Some(dir) = dir {
+        env.set_dir(dir);
+      }
+      lang::eval(src,&tree,&mut env).err().unwrap_or_default()
+    },
+    false => errs
+  };
+
+  errs.iter().map(|e|{
+    let mut message = e.msg().to_string();
+    if let Some(help) = e.help() {
+      message = format!("{}\nhelp: {}",message,help);
+    }
+
+    //errors from another file go on the line that loaded it
+    let span = match e.file_path() {
+      None => e.span(),
+      Some(path) => {
+        message = format!("in {} line {}: {}",path,e.line_num(),message);
+        do_file_span(&tree,path).unwrap_or(0..0)
+      }
+    };
+
+    Diagnostic{
+      range:lines.range(span),
+      severity:Some(DiagnosticSeverity::ERROR),
+      code:Some(NumberOrString::String(e.code().to_string())),
+      source:Some("sgv".to_string()),
+      message,
+      ..Default::default()
+    }
+  }).collect()
+}
+
+//the folder a file:// document is in, so the files it loads get found from there
+pub fn doc_dir(uri:&str) -> Option<PathBuf> {
+  let mut rest = uri.strip_prefix("file://")?.as_bytes();
+  let mut path = vec![];
+  while let Some((&b,tail)) = rest.split_first() {
+    let escaped = tail.get(..2).filter(|_|b == b'%')
+    .and_then(|hex|u8::from_str_radix(std::str::from_utf8(hex).ok()?,16).ok());
+
+    match escaped {
+      Some(byte) => {
+        path.push(byte);
+        rest = &tail[2..];
+      },
+      None => {
+        path.push(b);
+        rest = tail;
+      }
+    }
+  }
+
+  let path = PathBuf::from(String::from_utf8(path).ok()?);
+  path.parent().map(|p|p.to_path_buf())
+}
+
+fn do_file_span(tree:&[Node<Stmt>],path:&str) -> Option<std::ops::Range<usize>> {
+  tree.iter().find_map(|s|match &s.item {
+    Stmt::DoFile(p) if p.item == path => Some(s.span.clone()),
+    _ => None
+  })
+  .or_else(||tree.iter().find(|s|matches!(s.item,Stmt::DoFile(_))).map(|s|s.span.clone()))
+}
+
+//the name under the cursor and where it was set, if it's a name
+pub fn definition(src:&str,at:usize) -> Option<(Node<String>,Node<String>,Node<Expr>)> {
+  let (tree,_) = lang::parse_partial(src);
+
+  let used = ast::name_refs(&tree).into_iter().find(|r|r.span.contains(&at));
+  let used = used.or_else(||assigned_names(&tree).into_iter().find(|nm|nm.span.contains(&at)))?;
+
+  let (nm,v) = ast::definition(&tree,&used.item,at)?;
+  Some((used,nm.clone(),v.clone()))
+}
+
+fn assigned_names(tree:&[Node<Stmt>]) -> Vec<Node<String>> {
+  let mut out = vec![];
+  for s in tree.iter() {
+    let v = match &s.item {
+      Stmt::Assign(nm,v) | Stmt::SlotName(nm,v) => {
+        out.push(nm.clone());
+        v
+      },
+      Stmt::SlotAssign(_,v) => v,
+      Stmt::Def(nm,params,v) => {
+        out.push(nm.clone());
+        out.extend(params.iter().cloned());
+        v
+      },
+      Stmt::For(nm,_,body) => {
+        out.push(nm.clone());
+        out.extend(assigned_names(body));
+        continue
+      },
+      _ => continue
+    };
+    seq_names(v,&mut out);
+  }
+  out
+}
+
+fn seq_names(e:&Node<Expr>,out:&mut Vec<Node<String>>) {
+  match &e.item {
+    Expr::Seq(assigns,_) => for (nm,v) in assigns.iter() {
+      out.push(nm.clone());
+      seq_names(v,out);
+    },
+    Expr::Bin(_,a,b) | Expr::Range(a,b) => {
+      seq_names(a,out);
+      seq_names(b,out);
+    },
+    Expr::Neg(v) => seq_names(v,out),
+    Expr::Call(_,args) => args.iter().for_each(|a|seq_names(a,out)),
+    _ => ()
+  }
+}
+
+//the whole assignment, like it was written
+pub fn hover(src:&str,at:usize) -> Option<(std::ops::Range<usize>,String)> {
+  let (used,nm,v) = definition(src,at)?;
+  let txt = &src[nm.span.start..v.span.end];
+  Some((used.span,format!("```\n{}\n```",txt)))
+}
+
+pub fn completions(src:&str) -> Vec<CompletionItem> {
+  let item = |label:&str,kind,detail:Option<String>| CompletionItem{
+    label:label.to_string(),
+    kind:Some(kind),
+    detail,
+    ..Default::default()
+  };
+
+  //the last time a name is set is the one that sticks
+  let (tree,_) = lang::parse_partial(src);
+  let mut seen = std::collections::HashSet::new();
+  let names : Vec<_> = tree.iter().rev().filter_map(|s|match &s.item {
+    Stmt::Assign(nm,v) if seen.insert(nm.item.clone()) => {
+      Some(item(&nm.item,CompletionItemKind::VARIABLE,Some(src[v.span.clone()].to_string())))
+    },
+    Stmt::SlotName(nm,v) if seen.insert(nm.item.clone()) => {
+      Some(item(&nm.item,CompletionItemKind::CONSTANT,Some(format!("slot {}",&src[v.span.clone()]))))
+    },
+    Stmt::Def(nm,_,v) if seen.insert(nm.item.clone()) => {
+      Some(item(&nm.item,CompletionItemKind::FUNCTION,Some(src[nm.span.start..v.span.end].to_string())))
+    },
+    _ => None
+  }).collect();
+
+  lang::KEYWORDS.iter().map(|kw|item(kw,CompletionItemKind::KEYWORD,None))
+  .chain(lang::FUNCTIONS.iter().map(|f|item(f,CompletionItemKind::FUNCTION,None)))
+  .chain(names)
+  .collect()
+}
+
+const TYPES : &[SemanticTokenType] = &[
+  SemanticTokenType::KEYWORD,
+  SemanticTokenType::VARIABLE,
+  SemanticTokenType::FUNCTION,
+  SemanticTokenType::NUMBER,
+  SemanticTokenType::ENUM_MEMBER,
+  SemanticTokenType::STRING,
+  SemanticTokenType::OPERATOR,
+  SemanticTokenType::COMMENT
+];
+
+pub fn legend() -> SemanticTokensLegend {
+  SemanticTokensLegend{
+    token_types:TYPES.to_vec(),
+    token_modifiers:vec![SemanticTokenModifier::DECLARATION]
+  }
+}
+
+fn token_type(t:Token) -> Option<u32> {
+  let tt = match t {
+    t if t.is_keyword() => SemanticTokenType::KEYWORD,
+    Token::Sym => SemanticTokenType::VARIABLE,
+    Token::Digits | Token::Decimal => SemanticTokenType::NUMBER,
+    Token::Note | Token::Chord | Token::Degree => SemanticTokenType::ENUM_MEMBER,
+    Token::Path | Token::Str => SemanticTokenType::STRING,
+    Token::Eq | Token::Dash | Token::Quest | Token::Pipe | Token::Comma |
+    Token::Plus | Token::Colon | Token::Star | Token::Slash |
+    Token::DotDot | Token::Semi => SemanticTokenType::OPERATOR,
+    _ => return None
+  };
+
+  TYPES.iter().position(|x|*x == tt).map(|i|i as u32)
+}
+
+//straight off the lexer, the comments are whatever is in the gaps between tokens
+pub fn semantic_tokens(lines:&Lines) -> Vec<SemanticToken> {
+  let src = lines.src();
+  let (tree,_) = lang::parse_partial(src);
+  let declared : Vec<_> = assigned_names(&tree).into_iter().map(|nm|nm.span.start).collect();
+  let comment = TYPES.iter().position(|x|*x == SemanticTokenType::COMMENT).unwrap_or(0) as u32;
+  let function = TYPES.iter().position(|x|*x == SemanticTokenType::FUNCTION).unwrap_or(0) as u32;
+
+  let mut found : Vec<(std::ops::Range<usize>,u32,u32)> = vec![];
+  let mut lx = Lexer::new(src);
+  let mut prev_end = 0;
+  loop {
+    let t = lx.next();
+    let span = if t == Token::Eof { src.len()..src.len() } else { lx.span() };
+
+    for c in comments(src,prev_end..span.start) {
+      found.push((c,comment,0));
+    }
+
+    if t == Token::Eof {
+      break;
+    }
+
+    prev_end = span.end;
+    let called = t == Token::Sym && lx.lookahead() == Token::OpenParen && lx.lookahead_span().start == span.end;
+    let tt = if called { Some(function) } else { token_type(t) };
+    if let Some(tt) = tt {
+      let modifiers = declared.contains(&span.start) as u32;
+      found.push((span,tt,modifiers));
+    }
+  }
+
+  let mut out = vec![];
+  let (mut prev_line,mut prev_col) = (0,0);
+  for (span,tt,modifiers) in found.into_iter() {
+    let start = lines.position(span.start);
+    let len = src[span].encode_utf16().count() as u32;
+    if start.line != prev_line {
+      prev_col = 0;
+    }
+
+    out.push(SemanticToken{
+      delta_line:start.line - prev_line,
+      delta_start:start.character - prev_col,
+      length:len,
+      token_type:tt,
+      token_modifiers_bitset:modifiers
+    });
+
+    prev_line = start.line;
+    prev_col = start.character;
+  }
+  out
+}
+
+//comments in a gap, one range per line since a block comment can go over a few
+fn comments(src:&str,gap:std::ops::Range<usize>) -> Vec<std::ops::Range<usize>> {
+  let txt = &src[gap.clone()];
+  let mut out = vec![];
+  let mut i = 0;
+  while i < txt.len() {
+    let rest = &txt[i..];
+    let len = if rest.starts_with('#') || rest.starts_with("//") {
+      rest.find('\n').unwrap_or(rest.len())
+    }
+    else if let Some(body) = rest.strip_prefix("/*") {
+      body.find("*/").map(|e|e + 4).unwrap_or(rest.len())
+    }
+    else {
+      i += rest.chars().next().map(|c|c.len_utf8()).unwrap_or(1);
+      continue;
+    };
+
+    let mut start = gap.start + i;
+    for line in txt[i..i + len].split_inclusive('\n') {
+      let trimmed = line.trim_end();
+      if !trimmed.is_empty() {
+        out.push(start..start + trimmed.len());
+      }
+      start += line.len();
+    }
+    i += len;
+  }
+  out
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn diags(src:&str,dir:Option<&Path>) -> Vec<Diagnostic> {
+    diagnostics(&Lines::new(src),dir)
+  }
+
+  fn code(d:&Diagnostic) -> &str {
+    match &d.code {
+      Some(NumberOrString::String(c)) => c,
+      _ => ""
+    }
+  }
+
+  #[test]
+  fn test_diagnostics() {
+    assert!(diags("X = (C_1 100)\n<0> = [X -]",None).is_empty());
+
+    //columns count utf-16, the 𝄞 takes two
+    let d = diags("n = \"é𝄞\" + nope",None);
+    assert_eq!(d.len(),1);
+    assert_eq!(code(&d[0]),"E005");
+    assert_eq!((d[0].range.start.line,d[0].range.start.character),(0,12));
+    assert_eq!(d[0].range.end.character,16);
+
+    //running out of input points at the very end
+    let src = "X = (C_1 100)\n<0> = [X -";
+    let d = diags(src,None);
+    assert_eq!(d.len(),1);
+    assert_eq!(code(&d[0]),"E003");
+    assert_eq!(d[0].range.end,Lines::new(src).position(src.len()));
+  }
+
+  #[test]
+  fn test_do_file_diagnostics() {
+    let dir = std::env::temp_dir().join("sgv_lsp_test_do_file");
+    std::fs::create_dir_all(&dir).expect("couldn't make the test folder");
+    std::fs::write(dir.join("drums.sgv"),"X = (C_1 100)\n").expect("couldn't write the test file");
+    std::fs::write(dir.join("loop.sgv"),"do_file loop.sgv\n").expect("couldn't write the test file");
+
+    //paths start from the document's folder, not wherever the server is running
+    assert!(diags("do_file drums.sgv\n<0> = [X]",Some(&dir)).is_empty());
+    assert_eq!(code(&diags("do_file drums.sgv",None)[0]),"E011");
+
+    //a file that loads itself is an error, not a crash
+    let d = diags("# loops\ndo_file loop.sgv",Some(&dir));
+    assert_eq!(d.len(),1);
+    assert_eq!(code(&d[0]),"E012");
+    assert_eq!(d[0].range.start.line,1);
+
+    let _ = std::fs::remove_dir_all(&dir);
+  }
+
+  #[test]
+  fn test_doc_dir() {
+    assert_eq!(doc_dir("file:///home/me/my%20sets/a.sgv"),Some(PathBuf::from("/home/me/my sets")));
+    assert_eq!(doc_dir("file:///home/%C3%A9/a.sgv"),Some(PathBuf::from("/home/é")));
+    assert_eq!(doc_dir("untitled:Untitled-1"),None);
+  }
+
+  #[test]
+  fn test_definition() {
+    let src = "X = (C_1 100)\nY = [X -]\nfill = {\n  X = (D_1 100)\n  [X]\n}";
+    let (used,nm,v) = definition(src,19).expect("should find X");
+    assert_eq!(used.span,19..20);
+    assert_eq!(nm.span,0..1);
+    assert_eq!(&src[v.span],"(C_1 100)");
+
+    //the closest one that was set before it wins
+    let at = src.rfind('X').expect("there's an X");
+    let (_,nm,_) = definition(src,at).expect("should find X");
+    assert_eq!(nm.span.start,src.find("  X").expect("there's an inner X") + 2);
+
+    assert!(definition(src,src.find("100").expect("there's a 100")).is_none());
+  }
+
+  #[test]
+  fn test_semantic_tokens() {
+    let var = TYPES.iter().position(|t|*t == SemanticTokenType::VARIABLE).unwrap_or(0) as u32;
+    let comment = TYPES.iter().position(|t|*t == SemanticTokenType::COMMENT).unwrap_or(0) as u32;
+
+    let toks = semantic_tokens(&Lines::new("# é𝄞\nX = 1 # hi"));
+    let found : Vec<_> = toks.iter().map(|t|(t.delta_line,t.delta_start,t.length,t.token_type,t.token_modifiers_bitset)).collect();
+    assert_eq!(found,[
+      (0,0,5,comment,0),
+      (1,0,1,var,1),
+      (0,2,1,token_type(Token::Eq).unwrap_or(0),0),
+      (0,2,1,token_type(Token::Digits).unwrap_or(0),0),
+      (0,2,4,comment,0)
+    ]);
+  }
+}
@@ -0,0 +1,80 @@
+use lsp_types::{Position,Range};
+
+//the parser works in byte offsets and lsp wants lines and
+//utf-16 columns, this does the back and forth for one document
+pub struct Lines<'a> {
+  src:&'a str,
+  starts:Vec<usize>
+}
+
+impl<'a> Lines<'a> {
+  pub fn new(src:&'a str) -> Self {
+    let starts = std::iter::once(0)
+    .chain(src.match_indices('\n').map(|(i,_)|i + 1))
+    .collect();
+
+    Self {
+      src,
+      starts
+    }
+  }
+
+  pub fn position(&self,offset:usize) -> Position {
+    let offset = offset.min(self.src.len());
+    let line = self.starts.partition_point(|s|*s <= offset) - 1;
+    let col = self.src[self.starts[line]..offset].encode_utf16().count();
+    Position::new(line as u32,col as u32)
+  }
+
+  pub fn range(&self,span:std::ops::Range<usize>) -> Range {
+    Range::new(self.position(span.start),self.position(span.end))
+  }
+
+  pub fn offset(&self,pos:Position) -> usize {
+    let Some(start) = self.starts.get(pos.line as usize) else {
+      return self.src.len();
+    };
+
+    let mut units = 0;
+    for (i,c) in self.src[*start..].char_indices() {
+      if units >= pos.character as usize || c == '\n' {
+        return start + i;
+      }
+      units += c.len_utf16();
+    }
+
+    self.src.len()
+  }
+
+  pub fn src(&self) -> &'a str {
+    self.src
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_positions() {
+    //é is one utf-16 unit and two bytes, 𝄞 is two units and four bytes
+    let src = "ab\né𝄞x\n";
+    let lines = Lines::new(src);
+
+    assert_eq!(lines.position(0),Position::new(0,0));
+    assert_eq!(lines.position(3),Position::new(1,0));
+    assert_eq!(lines.position(5),Position::new(1,1));
+    assert_eq!(lines.position(9),Position::new(1,3));
+    assert_eq!(lines.position(src.len()),Position::new(2,0));
+    assert_eq!(lines.position(src.len() + 10),Position::new(2,0));
+
+    for offset in [0,1,3,5,9,10,src.len()] {
+      assert_eq!(lines.offset(lines.position(offset)),offset);
+    }
+
+    //past the end of a line or the file sticks to the end
+    assert_eq!(lines.offset(Position::new(0,40)),2);
+    assert_eq!(lines.offset(Position::new(9,0)),src.len());
+    assert_eq!(lines.range(5..9),Range::new(Position::new(1,1),Position::new(1,3)));
+  }
+}
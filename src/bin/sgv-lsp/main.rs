@@ -0,0 +1,146 @@
+use std::collections::HashMap;
+use std::error::Error;
+
+use lsp_server::{Connection,ErrorCode,Message,Notification,Request,Response};
+use lsp_types::{
+  notification::{self,Notification as _},
+  request::{self,Request as _},
+  CompletionOptions,
+  GotoDefinitionResponse,
+  Hover,
+  HoverContents,
+  HoverProviderCapability,
+  Location,
+  MarkupContent,
+  MarkupKind,
+  OneOf,
+  PublishDiagnosticsParams,
+  SemanticTokens,
+  SemanticTokensFullOptions,
+  SemanticTokensOptions,
+  SemanticTokensResult,
+  ServerCapabilities,
+  TextDocumentSyncCapability,
+  TextDocumentSyncKind
+};
+
+mod analysis;
+mod lines;
+
+use lines::Lines;
+
+//speaks lsp over stdin and stdout, the editor starts it
+fn main() -> Result<(),Box<dyn Error + Sync + Send>> {
+  let (conn,io) = Connection::stdio();
+  conn.initialize(serde_json::to_value(capabilities())?)?;
+
+  //keyed on the uri text, Uri itself doesn't make a good key
+  let mut docs : HashMap<String,String> = HashMap::new();
+  for msg in &conn.receiver {
+    match msg {
+      Message::Request(req) => {
+        if conn.handle_shutdown(&req)? {
+          break;
+        }
+        conn.sender.send(Message::Response(request(&docs,req)))?;
+      },
+      Message::Notification(note) => {
+        if let Some(params) = notification(&mut docs,note) {
+          let out = Notification::new(notification::PublishDiagnostics::METHOD.to_string(),params);
+          conn.sender.send(Message::Notification(out))?;
+        }
+      },
+      Message::Response(_) => ()
+    }
+  }
+
+  //the writer thread only stops once the connection is gone
+  drop(conn);
+  io.join()?;
+  Ok(())
+}
+
+fn capabilities() -> ServerCapabilities {
+  ServerCapabilities{
+    text_document_sync:Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+    definition_provider:Some(OneOf::Left(true)),
+    hover_provider:Some(HoverProviderCapability::Simple(true)),
+    completion_provider:Some(CompletionOptions::default()),
+    semantic_tokens_provider:Some(SemanticTokensOptions{
+      legend:analysis::legend(),
+      full:Some(SemanticTokensFullOptions::Bool(true)),
+      ..Default::default()
+    }.into()),
+    ..Default::default()
+  }
+}
+
+//keeps the open documents up to date, any change means new diagnostics
+fn notification(docs:&mut HashMap<String,String>,note:Notification) -> Option<PublishDiagnosticsParams> {
+  let (uri,txt) = match note.method.as_str() {
+    notification::DidOpenTextDocument::METHOD => {
+      let p = note.extract::<lsp_types::DidOpenTextDocumentParams>(notification::DidOpenTextDocument::METHOD).ok()?;
+      (p.text_document.uri,Some(p.text_document.text))
+    },
+    notification::DidChangeTextDocument::METHOD => {
+      let p = note.extract::<lsp_types::DidChangeTextDocumentParams>(notification::DidChangeTextDocument::METHOD).ok()?;
+      //with full sync the last change is the whole document
+      (p.text_document.uri,p.content_changes.into_iter().last().map(|c|c.text))
+    },
+    notification::DidCloseTextDocument::METHOD => {
+      let p = note.extract::<lsp_types::DidCloseTextDocumentParams>(notification::DidCloseTextDocument::METHOD).ok()?;
+      docs.remove(p.text_document.uri.as_str());
+      return Some(PublishDiagnosticsParams::new(p.text_document.uri,vec![],None));
+    },
+    _ => return None
+  };
+
+  let txt = txt?;
+  let dir = analysis::doc_dir(uri.as_str());
+  let diags = analysis::diagnostics(&Lines::new(&txt),dir.as_deref());
+  docs.insert(uri.as_str().to_string(),txt);
+  Some(PublishDiagnosticsParams::new(uri,diags,None))
+}
+
+fn request(docs:&HashMap<String,String>,req:Request) -> Response {
+  match req.method.as_str() {
+    request::GotoDefinition::METHOD => respond::<request::GotoDefinition>(req,|p|{
+      let pos = p.text_document_position_params;
+      let src = docs.get(pos.text_document.uri.as_str())?;
+      let lines = Lines::new(src);
+      let (_,nm,_) = analysis::definition(src,lines.offset(pos.position))?;
+      Some(GotoDefinitionResponse::Scalar(Location::new(pos.text_document.uri,lines.range(nm.span))))
+    }),
+    request::HoverRequest::METHOD => respond::<request::HoverRequest>(req,|p|{
+      let pos = p.text_document_position_params;
+      let src = docs.get(pos.text_document.uri.as_str())?;
+      let lines = Lines::new(src);
+      let (span,txt) = analysis::hover(src,lines.offset(pos.position))?;
+      Some(Hover{
+        contents:HoverContents::Markup(MarkupContent{kind:MarkupKind::Markdown,value:txt}),
+        range:Some(lines.range(span))
+      })
+    }),
+    request::Completion::METHOD => respond::<request::Completion>(req,|p|{
+      let src = docs.get(p.text_document_position.text_document.uri.as_str())?;
+      Some(analysis::completions(src).into())
+    }),
+    request::SemanticTokensFullRequest::METHOD => respond::<request::SemanticTokensFullRequest>(req,|p|{
+      let src = docs.get(p.text_document.uri.as_str())?;
+      let data = analysis::semantic_tokens(&Lines::new(src));
+      Some(SemanticTokensResult::Tokens(SemanticTokens{result_id:None,data}))
+    }),
+    _ => Response::new_err(req.id,ErrorCode::MethodNotFound as i32,format!("we don't do {}",req.method))
+  }
+}
+
+fn respond<R>(req:Request,f:impl FnOnce(R::Params) -> R::Result) -> Response
+where
+  R:request::Request
+{
+  let id = req.id.clone();
+  match req.extract::<R::Params>(R::METHOD) {
+    Ok((id,params)) => Response::new_ok(id,f(params)),
+    Err(e) => Response::new_err(id,ErrorCode::InvalidParams as i32,format!("{:?}",e))
+  }
+}
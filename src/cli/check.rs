@@ -0,0 +1,285 @@
+use std::collections::{BTreeMap,BTreeSet,HashMap};
+use std::path::Path;
+
+use colored::Colorize;
+
+use crate::repl::editor::print_errors;
+
+#[derive(Default)]
+struct Slot {
+  name:Option<String>,
+  len:usize,
+  notes:usize,
+  div:Option<usize>,
+  sync:Option<usize>,
+  output:Option<usize>,
+  playing:bool
+}
+
+//writes down everything a file asks for instead of doing it,
+//so it runs the same with or without any midi plugged in.
+//listing ports is left to the runtime's do-nothing defaults
+#[derive(Default)]
+struct Recorder {
+  bpm:Option<f64>,
+  //bpm and how many bars it takes to get there
+  ramp:Option<(f64,usize)>,
+  key:Option<lang::Key>,
+  outputs:Vec<(usize,u8)>,
+  clocks:BTreeSet<usize>,
+  lps:Vec<usize>,
+  clock_in:Option<usize>,
+  paused:bool,
+  bar:Option<usize>,
+  slots:BTreeMap<usize,Slot>
+}
+
+impl lang::Runtime for Recorder {
+  fn assign_slot(&mut self,snum:usize,len:usize,evs:HashMap<usize,Vec<lang::SeqNote>>) {
+    let slot = self.slots.entry(snum).or_default();
+    slot.len = len;
+    slot.notes = evs.values().map(|ns|ns.len()).sum();
+  }
+
+  fn set_bpm(&mut self,bpm_num:f64) {
+    self.bpm = Some(bpm_num);
+    self.ramp = None;
+  }
+
+  fn ramp_bpm(&mut self,bpm_num:f64,bars:usize) {
+    self.ramp = Some((bpm_num,bars));
+  }
+
+  //there's nobody here to tap
+  fn tap(&mut self) {}
+
+  fn set_div(&mut self,slots:&lang::Slots,div_num:usize) {
+    self.each(slots,|s|s.div = Some(div_num));
+  }
+
+  fn set_sync(&mut self,slots:&lang::Slots,sync_num:usize) {
+    self.each(slots,|s|s.sync = Some(sync_num));
+  }
+
+  fn set_output(&mut self,slots:&lang::Slots,out_num:usize) {
+    self.each(slots,|s|s.output = Some(out_num));
+  }
+
+  fn play_slots(&mut self,slots:&lang::Slots) {
+    self.each(slots,|s|s.playing = true);
+  }
+
+  fn stop_slots(&mut self,slots:&lang::Slots) {
+    self.each(slots,|s|s.playing = false);
+  }
+
+  fn pause(&mut self) {
+    self.paused = true;
+  }
+
+  fn resume(&mut self) {
+    self.paused = false;
+  }
+
+  fn restart(&mut self) {
+    self.paused = false;
+    self.bar = None;
+  }
+
+  fn locate(&mut self,bar:usize) {
+    self.bar = Some(bar);
+  }
+
+
+  fn open_out(&mut self,out_num:usize,channel:u8) {
+    self.outputs.push((out_num,channel));
+  }
+
+  fn open_lp(&mut self,lp_num:usize) {
+    self.lps.push(lp_num);
+  }
+
+  fn clock_in(&mut self,in_num:Option<usize>) {
+    self.clock_in = in_num;
+  }
+
+  fn clock_out(&mut self,out_num:usize,on:bool) {
+    match on {
+      true => self.clocks.insert(out_num),
+      false => self.clocks.remove(&out_num)
+    };
+  }
+
+  fn set_key(&mut self,key:lang::Key) {
+    self.key = Some(key);
+  }
+
+  fn name_slot(&mut self,slot:usize,nm:&str) {
+    for s in self.slots.values_mut().filter(|s|s.name.as_deref() == Some(nm)) {
+      s.name = None;
+    }
+    self.slots.entry(slot).or_default().name = Some(nm.to_string());
+  }
+}
+
+impl Recorder {
+  //there's no fixed number of slots here, so "all" is every slot we've heard of
+  fn each(&mut self,slots:&lang::Slots,f:impl Fn(&mut Slot)) {
+    match slots {
+      lang::Slots::All => self.slots.values_mut().for_each(f),
+      lang::Slots::These(ns) => ns.iter().for_each(|n|f(self.slots.entry(*n).or_default()))
+    }
+  }
+
+  //one line for everything the file set up
+  fn report(&self) -> Vec<String> {
+    let mut lines = vec![];
+
+    if let Some(bpm) = self.bpm {
+      lines.push(format!("  bpm {}",bpm));
+    }
+
+    if let Some((bpm,bars)) = self.ramp {
+      lines.push(format!("  going to bpm {} over {} bars",bpm,bars));
+    }
+
+    if let Some(key) = self.key {
+      lines.push(format!("  key {}",key));
+    }
+
+    for (i,(out_num,chan)) in self.outputs.iter().enumerate() {
+      let clock = if self.clocks.contains(&i) { ", sends clock" } else { "" };
+      lines.push(format!("  output {}: port {} channel {}{}",i,out_num,chan,clock));
+    }
+
+    if let Some(in_num) = self.clock_in {
+      lines.push(format!("  following the clock on input port {}",in_num));
+    }
+
+    match (self.paused,self.bar) {
+      (true,Some(bar)) => lines.push(format!("  paused at bar {}",bar)),
+      (true,None) => lines.push("  paused".to_string()),
+      (false,Some(bar)) => lines.push(format!("  playing from bar {}",bar)),
+      (false,None) => ()
+    }
+
+    for lp in self.lps.iter() {
+      lines.push(format!("  launchpad on port {}",lp));
+    }
+
+    for (snum,s) in self.slots.iter() {
+      let nm = s.name.as_ref().map(|nm|format!(" ({})",nm)).unwrap_or_default();
+      let mut txt = match s.len {
+        0 => format!("  slot {}{}: nothing assigned",snum,nm),
+        len => format!("  slot {}{}: {} steps, {} notes",snum,nm,len,s.notes)
+      };
+      if let Some(div) = s.div {
+        txt += &format!(", div {}",div);
+      }
+      if let Some(sync) = s.sync {
+        txt += &format!(", sync {}",sync);
+      }
+      if let Some(out) = s.output {
+        match out < self.outputs.len() {
+          true => txt += &format!(", output {}",out),
+          false => txt += &format!(", output {} {}",out,"(never opened)".yellow())
+        }
+      }
+      if s.playing {
+        txt += ", playing";
+      }
+      lines.push(txt);
+    }
+
+    lines
+  }
+}
+
+//runs one file's text, do_file paths start from dir
+fn record<'a>(src:&'a str,dir:Option<&Path>) -> (Result<(),Vec<lang::Error<'a>>>,lang::Env<Recorder>) {
+  let mut env = lang::Env::new(Recorder::default());
+  if let Some(dir) = dir {
+    env.set_dir(dir);
+  }
+  let res = lang::parse(src,&mut env);
+  (res,env)
+}
+
+//runs every file on its own and says what it would have set up
+pub fn run(args:&[String]) -> bool {
+  if args.is_empty() {
+    println!("which files should we check?");
+    return false;
+  }
+
+  let mut ok = true;
+  for path in args.iter() {
+    let src = match std::fs::read_to_string(path) {
+      Ok(src) => src,
+      Err(e) => {
+        println!("{} couldn't read {}: {}","✗".bright_red(),path,e);
+        ok = false;
+        continue;
+      }
+    };
+
+    let (res,env) = record(&src,Path::new(path).parent());
+    match res {
+      Ok(()) => println!("{} {}","✓".green(),path),
+      Err(errs) => {
+        println!("{} {}","✗".bright_red(),path);
+        print_errors(&errs);
+        ok = false;
+      }
+    }
+
+    for line in env.runtime().report() {
+      println!("{}",line);
+    }
+  }
+
+  ok
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const SET:&str = "bpm 120
+key D minor
+open_output 1 2
+clock_out 0 on
+slot kick = 0
+K = (C_1 100)
+H = (D_2 80)
+<kick> = [K - K -]
+<1> = [H]
+div 1 2
+set_output kick 0
+set_output 1 3
+play kick
+locate 4
+";
+
+  #[test]
+  fn test_report() {
+    colored::control::set_override(false);
+    let (res,env) = record(SET,None);
+    assert!(res.is_ok());
+    assert_eq!(env.runtime().report(),[
+      "  bpm 120",
+      "  key D_3 minor",
+      "  output 0: port 1 channel 2, sends clock",
+      "  playing from bar 4",
+      "  slot 0 (kick): 4 steps, 2 notes, output 0, playing",
+      "  slot 1: 1 steps, 1 notes, div 2, output 3 (never opened)"
+    ]);
+  }
+
+  #[test]
+  fn test_report_errors() {
+    let (res,env) = record("bpm 90\nplay nope\n",None);
+    assert_eq!(res.map_err(|errs|errs.len()),Err(1));
+    assert_eq!(env.runtime().report(),["  bpm 90"]);
+  }
+}
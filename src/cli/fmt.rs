@@ -0,0 +1,74 @@
+use std::io::Read;
+
+use crate::repl::editor::print_errors;
+
+//rewrites the files in place, with --check it just says which ones
+//would change and fails if any would
+pub fn run(args:&[String]) -> bool {
+  let check = args.iter().any(|a|a == "--check");
+  let files : Vec<_> = args.iter().filter(|a|*a != "--check").collect();
+
+  if files.is_empty() {
+    return stdin(check);
+  }
+
+  let mut ok = true;
+  for path in files.into_iter() {
+    let src = match std::fs::read_to_string(path) {
+      Ok(src) => src,
+      Err(e) => {
+        println!("couldn't read {}: {}",path,e);
+        ok = false;
+        continue;
+      }
+    };
+
+    let out = match lang::format(&src) {
+      Ok(out) => out,
+      Err(errs) => {
+        println!("{} doesn't parse so it's staying how it is",path);
+        print_errors(&errs);
+        ok = false;
+        continue;
+      }
+    };
+
+    if out == src {
+      continue;
+    }
+
+    if check {
+      println!("{} needs formatting",path);
+      ok = false;
+    }
+    else if let Err(e) = std::fs::write(path,out) {
+      println!("couldn't write {}: {}",path,e);
+      ok = false;
+    }
+    else {
+      println!("formatted {}",path);
+    }
+  }
+
+  ok
+}
+
+fn stdin(check:bool) -> bool {
+  let mut src = String::new();
+  if let Err(e) = std::io::stdin().read_to_string(&mut src) {
+    println!("couldn't read stdin: {}",e);
+    return false;
+  }
+
+  match lang::format(&src) {
+    Ok(out) if check => out == src,
+    Ok(out) => {
+      print!("{}",out);
+      true
+    },
+    Err(errs) => {
+      print_errors(&errs);
+      false
+    }
+  }
+}
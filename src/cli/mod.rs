@@ -0,0 +1,31 @@
+use std::process::ExitCode;
+
+mod fmt;
+mod check;
+
+//"sgv" on its own is the repl, anything after it is a command that
+//does its thing and quits without opening any midi
+pub fn run(args:&[String]) -> Option<ExitCode> {
+  let (cmd,rest) = args.split_first()?;
+  let ok = match cmd.as_str() {
+    "fmt" => fmt::run(rest),
+    "check" => check::run(rest),
+    _ => {
+      println!("don't know the command \"{}\"",cmd);
+      usage();
+      false
+    }
+  };
+
+  match ok {
+    true => Some(ExitCode::SUCCESS),
+    false => Some(ExitCode::FAILURE)
+  }
+}
+
+fn usage() {
+  println!("usage:");
+  println!("  sgv                              start the repl");
+  println!("  sgv fmt [--check] [files...]     tidy up set files, with no files it does stdin to stdout");
+  println!("  sgv check files...               run set files without any midi and say what they'd set up");
+}
@@ -1,14 +1,21 @@
 use std::error::Error;
+use std::process::ExitCode;
 mod sequence_types;
 mod repl;
 mod player;
+mod cli;
+
+fn main() -> Result<ExitCode,Box<dyn Error>> {
+  let args : Vec<String> = std::env::args().skip(1).collect();
+  if let Some(code) = cli::run(&args) {
+    return Ok(code);
+  }
 
-fn main() -> Result<(),Box<dyn Error>> {
   let (tx,_rx,handle) = player::start();
 
   repl::start_main(player::Rt::new(tx.clone()))?;
  
   let _ = tx.send(player::SeqCmd::Quit);
   handle.join().expect("join broke");
-  Ok(())
+  Ok(ExitCode::SUCCESS)
 }
@@ -0,0 +1,69 @@
+use std::sync::mpsc::{channel,Sender,Receiver};
+use std::time::Duration;
+
+//what we care about from another box's clock
+pub enum ClockMsg {
+  Tick,
+  Start,
+  Stop,
+  Continue,
+  //in 16th notes like song position pointer has it
+  Position(usize)
+}
+
+pub struct ClockIn {
+  _conn:midir::MidiInputConnection<Sender<ClockMsg>>,
+  rx:Receiver<ClockMsg>
+}
+
+fn clock_cb(_time:u64,msg:&[u8],tx:&mut Sender<ClockMsg>) {
+  let cmsg = match msg {
+    [0xF8] => ClockMsg::Tick,
+    [0xFA] => ClockMsg::Start,
+    [0xFB] => ClockMsg::Continue,
+    [0xFC] => ClockMsg::Stop,
+    [0xF2,lsb,msb] => ClockMsg::Position(((*msb as usize) << 7) | *lsb as usize),
+    _ => return
+  };
+  let _ = tx.send(cmsg);
+}
+
+impl ClockIn {
+  pub fn open(in_num:usize) -> Result<Self,ClockInError> {
+    let mut midi_in = midir::MidiInput::new("clock in").map_err(|_|ClockInError::InPort)?;
+    midi_in.ignore(midir::Ignore::SysexAndActiveSense);
+
+    let ports = midi_in.ports();
+    let port = ports.get(in_num).ok_or(ClockInError::NoPort)?;
+    let (tx,rx) = channel();
+    let _conn = midi_in.connect(port,"clock in connection",clock_cb,tx)
+    .map_err(|_|ClockInError::Connect)?;
+
+    Ok(Self{_conn,rx})
+  }
+
+  //gives up after a bit when nothing comes, so commands
+  //still get looked at when the other box stops its clock
+  pub fn next(&mut self) -> Option<ClockMsg> {
+    self.rx.recv_timeout(Duration::from_millis(20)).ok()
+  }
+}
+
+#[derive(Debug)]
+pub enum ClockInError {
+  InPort,
+  NoPort,
+  Connect
+}
+
+impl std::fmt::Display for ClockInError {
+  fn fmt(&self,f:&mut std::fmt::Formatter) -> Result<(),std::fmt::Error> {
+    match self {
+      Self::InPort => write!(f,"couldn't make an input"),
+      Self::NoPort => write!(f,"there's no input with that number"),
+      Self::Connect => write!(f,"couldn't connect to the input")
+    }
+  }
+}
+
+impl std::error::Error for ClockInError {}
@@ -50,10 +50,16 @@ fn lpad_cb(_time:u64,msg:&[u8],port:&mut std::sync::mpsc::Sender<SeqCmd>) {
   let [status,nn,vel] = msg else { todo!("weird midi message from launchpad") };
 
   match status {
-    /*0xB0 => {
-      let col = nn-104;
+    //the first round button along the top taps the tempo
+    0xB0 => {
+      let col = nn.wrapping_sub(104);
       let hit = *vel != 0;
-    },*/
+
+      if col == 0 && hit {
+        let _ = port.send(SeqCmd::Tap(std::time::Instant::now()))
+        .inspect_err(|_|println!("lp port broke"));
+      }
+    },
 
     0x90 => {
       let row = (nn & 0xF0)>>4;
@@ -12,6 +12,8 @@ use crate::sequence_types as sqt;
 mod time;
 mod voicer;
 mod slot;
+mod song;
+mod clock_in;
 mod sequencer;
 mod controllers;
 
@@ -24,13 +26,26 @@ pub use voicer::Voicer;
 
 pub enum SeqCmd {
   PushOutput(Voicer),
-  SetBpm(usize),
+  SetBpm(f64),
+  RampBpm(f64,usize),
+  //stamped when it happens, the sequencer might not look for a tick
+  Tap(std::time::Instant),
+  SetKey(lang::Key),
   AssignSlot(usize,Box<dyn Sequence>),
-  AssignOutput(usize,usize),
-  PlaySlot(usize),
-  StopSlot(usize),
-  SetSync(usize,usize),
-  SetDiv(usize,usize),
+  //the slot ones come as a batch so they all change on the same tick
+  AssignOutput(lang::Slots,usize),
+  PlaySlots(lang::Slots),
+  StopSlots(lang::Slots),
+  SetSync(lang::Slots,usize),
+  SetDiv(lang::Slots,usize),
+  NameSlot(usize,String),
+  ClockOut(usize,bool),
+  ClockIn(Option<clock_in::ClockIn>),
+  ClockStats,
+  Pause,
+  Resume,
+  Restart,
+  Locate(usize),
   ToggleSlot(usize),
   Launchpad(controllers::Lpadout),
   
@@ -62,24 +77,36 @@ impl lang::Runtime for Rt {
     self.cmd_tx.send(SeqCmd::AssignSlot(snum,seq)).expect("send failed");
   }
 
-  fn set_bpm(&mut self,bpm_num:usize) {
+  fn set_bpm(&mut self,bpm_num:f64) {
     self.cmd_tx.send(SeqCmd::SetBpm(bpm_num)).expect("send failed");
   }
 
-  fn set_div(&mut self,slot:usize,div_num:usize) {
-    self.cmd_tx.send(SeqCmd::SetDiv(slot,div_num)).expect("send failed");
+  fn ramp_bpm(&mut self,bpm_num:f64,bars:usize) {
+    self.cmd_tx.send(SeqCmd::RampBpm(bpm_num,bars)).expect("send failed");
   }
 
-  fn set_sync(&mut self,slot:usize,sync_num:usize) {
-    self.cmd_tx.send(SeqCmd::SetSync(slot,sync_num)).expect("send failed");
+  fn tap(&mut self) {
+    self.cmd_tx.send(SeqCmd::Tap(std::time::Instant::now())).expect("send failed");
   }
 
-  fn play_slot(&mut self,slot:usize) {
-    self.cmd_tx.send(SeqCmd::PlaySlot(slot)).expect("send failed");
+  fn set_key(&mut self,key:lang::Key) {
+    self.cmd_tx.send(SeqCmd::SetKey(key)).expect("send failed");
   }
 
-  fn stop_slot(&mut self,slot:usize){ 
-    self.cmd_tx.send(SeqCmd::StopSlot(slot)).expect("send failed");
+  fn set_div(&mut self,slots:&lang::Slots,div_num:usize) {
+    self.cmd_tx.send(SeqCmd::SetDiv(slots.clone(),div_num)).expect("send failed");
+  }
+
+  fn set_sync(&mut self,slots:&lang::Slots,sync_num:usize) {
+    self.cmd_tx.send(SeqCmd::SetSync(slots.clone(),sync_num)).expect("send failed");
+  }
+
+  fn play_slots(&mut self,slots:&lang::Slots) {
+    self.cmd_tx.send(SeqCmd::PlaySlots(slots.clone())).expect("send failed");
+  }
+
+  fn stop_slots(&mut self,slots:&lang::Slots){ 
+    self.cmd_tx.send(SeqCmd::StopSlots(slots.clone())).expect("send failed");
   }
   
   fn list_outs(&mut self) {
@@ -114,8 +141,59 @@ impl lang::Runtime for Rt {
     self.cmd_tx.send(SeqCmd::PushOutput(v)).expect("send failed");
   }
 
-  fn set_output(&mut self,lot:usize,out:usize) {
-    self.cmd_tx.send(SeqCmd::AssignOutput(lot,out)).expect("send failed");
+  fn name_slot(&mut self,slot:usize,nm:&str) {
+    self.cmd_tx.send(SeqCmd::NameSlot(slot,nm.to_string())).expect("send failed");
+  }
+
+  fn set_output(&mut self,slots:&lang::Slots,out:usize) {
+    self.cmd_tx.send(SeqCmd::AssignOutput(slots.clone(),out)).expect("send failed");
+  }
+
+  fn clock_out(&mut self,out_num:usize,on:bool) {
+    self.cmd_tx.send(SeqCmd::ClockOut(out_num,on)).expect("send failed");
+  }
+
+  fn list_ins(&mut self) {
+    let midi_in = midir::MidiInput::new("lister").expect("failed to create midi input");
+    let ports = midi_in.ports();
+    for (i,p) in ports.iter().enumerate() {
+      match midi_in.port_name(p) {
+        Ok(n) => println!("{}: {}",i,n),
+        Err(e) => println!("{}: Couldn't get name:{}",i,e)
+      }
+    }
+  }
+
+  fn clock_in(&mut self,in_num:Option<usize>) {
+    let clock = match in_num.map(clock_in::ClockIn::open) {
+      Some(Ok(c)) => Some(c),
+      Some(Err(e)) => {
+        println!("couldn't follow that clock: {}",e);
+        return;
+      },
+      None => None
+    };
+    self.cmd_tx.send(SeqCmd::ClockIn(clock)).expect("send failed");
+  }
+
+  fn clock_stats(&mut self) {
+    self.cmd_tx.send(SeqCmd::ClockStats).expect("send failed");
+  }
+
+  fn pause(&mut self) {
+    self.cmd_tx.send(SeqCmd::Pause).expect("send failed");
+  }
+
+  fn resume(&mut self) {
+    self.cmd_tx.send(SeqCmd::Resume).expect("send failed");
+  }
+
+  fn restart(&mut self) {
+    self.cmd_tx.send(SeqCmd::Restart).expect("send failed");
+  }
+
+  fn locate(&mut self,bar:usize) {
+    self.cmd_tx.send(SeqCmd::Locate(bar)).expect("send failed");
   }
 
   fn open_lp(&mut self,_lp_num:usize) {
@@ -132,22 +210,34 @@ impl lang::Runtime for Rt {
 fn seq_thread(rx:Receiver<SeqCmd>,tx:Sender<SeqRsp>,mut seq:Sequencer) {
   let _ = tx.send(SeqRsp::Done);
 
+  //with another box's clock waiting can end without it being time to tick
+  let mut due = true;
   let loop_res : Result<(),usize> = 'main: loop {
-    match seq.tick() {
-      Ok(()) => (),
-      Err(n) => break Err(n)
-    };
+    if due && let Err(n) = seq.tick() {
+      break Err(n);
+    }
 
     for msg in rx.try_iter() {
       match msg {
         SeqCmd::SetBpm(bpm) => { seq.set_bpm(bpm); },
+        SeqCmd::RampBpm(bpm,bars) => { seq.ramp_bpm(bpm,bars); },
+        SeqCmd::Tap(at) => { seq.tap(at); },
+        SeqCmd::SetKey(key) => { seq.set_key(key); },
         SeqCmd::PushOutput(v) => { seq.push_output(v); },
         SeqCmd::AssignSlot(n,bds) => { seq.assign_slot(n,bds); },
-        SeqCmd::AssignOutput(n,u) => { seq.assign_slot_output(n,u); },
-        SeqCmd::PlaySlot(n) => { seq.play_slot(n); },
-        SeqCmd::StopSlot(n) => { seq.stop_slot(n); }
-        SeqCmd::SetSync(n,sync) => {seq.set_sync(n,sync);},
-        SeqCmd::SetDiv(n,div) => {seq.set_div(n,div);},
+        SeqCmd::AssignOutput(ns,u) => { seq.assign_slot_output(&ns,u); },
+        SeqCmd::PlaySlots(ns) => { seq.play_slots(&ns); },
+        SeqCmd::StopSlots(ns) => { seq.stop_slots(&ns); }
+        SeqCmd::SetSync(ns,sync) => {seq.set_sync(&ns,sync);},
+        SeqCmd::SetDiv(ns,div) => {seq.set_div(&ns,div);},
+        SeqCmd::NameSlot(n,nm) => { seq.name_slot(n,nm); },
+        SeqCmd::ClockOut(n,on) => { seq.clock_out(n,on); },
+        SeqCmd::ClockIn(c) => { seq.clock_in(c); },
+        SeqCmd::ClockStats => { seq.print_clock_stats(); },
+        SeqCmd::Pause => { seq.pause(); },
+        SeqCmd::Resume => { seq.resume(); },
+        SeqCmd::Restart => { seq.start(); },
+        SeqCmd::Locate(bar) => { seq.locate_bar(bar); },
         SeqCmd::ToggleSlot(n) => { seq.toggle_slot(n); }
         SeqCmd::Launchpad(lp) => { seq.add_lp(lp); }
         SeqCmd::Quit => { 
@@ -157,7 +247,7 @@ fn seq_thread(rx:Receiver<SeqCmd>,tx:Sender<SeqRsp>,mut seq:Sequencer) {
       }
     }
 
-    seq.wait();
+    due = seq.wait();
   };
 
   let _ = match loop_res {
@@ -171,7 +261,7 @@ pub fn start() -> (Sender<SeqCmd>,Receiver<SeqRsp>,std::thread::JoinHandle<()>)
   let (rsp_tx,rsp_rx) = channel::<SeqRsp>();
 
   let handle = std::thread::spawn(move ||{
-    seq_thread(cmd_rx,rsp_tx,Sequencer::init(120,64,None));
+    seq_thread(cmd_rx,rsp_tx,Sequencer::init(120.0,lang::SLOT_COUNT,None));
   });
 
   (cmd_tx,rsp_rx,handle)
@@ -1,6 +1,8 @@
 use super::{
-  voicer::Voicer,
-  time::TimeBuddy,
+  voicer::{Voicer,Clock},
+  time::{TimeBuddy,Source,Ramp,Tapper},
+  clock_in::{ClockIn,ClockMsg},
+  song::{self,Song},
   slot::{
     Slot,
     SlotState
@@ -15,13 +17,18 @@ use super::{
 pub struct Sequencer {
   outs:Vec<Voicer>,
   slots:Vec<Slot>,
-  timer:TimeBuddy,
+  source:Source,
+  bpm:f64,
+  ramp:Option<Ramp>,
+  taps:Tapper,
   lp:Option<Lpadout>,
-  sync_ticks:usize
+  sync_ticks:usize,
+  song:Song,
+  key:lang::Key
 }
 
 impl Sequencer {
-  pub fn init(bpm:usize,slot_count:usize,lp:Option<Lpadout>) -> Self {
+  pub fn init(bpm:f64,slot_count:usize,lp:Option<Lpadout>) -> Self {
     let mut slots = vec![];
     for _ in 0..slot_count {
       slots.push(Slot::default());
@@ -30,26 +37,101 @@ impl Sequencer {
     Sequencer {
       outs:vec![],
       slots,
-      timer: TimeBuddy::new(bpm),
+      source:Source::Internal(TimeBuddy::new(bpm)),
+      bpm,
+      ramp:None,
+      taps:Tapper::default(),
       lp,
-      sync_ticks:0
+      sync_ticks:0,
+      song:Song::new(),
+      key:lang::Key::default()
     }
   }
 
-  pub fn set_bpm(&mut self,bpm:usize) {
-    self.timer = TimeBuddy::new(bpm)
+  //when another box has the clock this only gets remembered for later
+  fn tempo(&mut self,bpm:f64) {
+    self.bpm = bpm;
+    if let Source::Internal(timer) = &mut self.source {
+      timer.set_bpm(bpm)
+    }
+  }
+
+  //a new bpm takes over from any ramp that was going
+  pub fn set_bpm(&mut self,bpm:f64) {
+    self.ramp = None;
+    self.tempo(bpm);
+  }
+
+  pub fn ramp_bpm(&mut self,bpm:f64,bars:usize) {
+    match bars {
+      0 => self.set_bpm(bpm),
+      _ => self.ramp = Some(Ramp::new(self.bpm,bpm,bars))
+    }
+  }
+
+  pub fn tap(&mut self,at:std::time::Instant) {
+    if let Some(bpm) = self.taps.tap(at) {
+      println!("tapped bpm {:.1}",bpm);
+      self.set_bpm(bpm);
+    }
+  }
+
+  pub fn print_clock_stats(&self) {
+    match &self.source {
+      Source::Internal(timer) => println!("{}",timer.stats()),
+      Source::External(_) => println!("following another clock, so there's nothing to time")
+    }
+  }
+
+  pub fn clock_in(&mut self,clock:Option<ClockIn>) {
+    self.source = match clock {
+      Some(c) => Source::External(c),
+      None => Source::Internal(TimeBuddy::new(self.bpm))
+    };
+  }
+
+  //scale degrees get worked out as they play, so this re-pitches them right away
+  pub fn set_key(&mut self,key:lang::Key) {
+    self.key = key
   }
 
   pub fn tick(&mut self) -> Result<(),usize> {
+    //clock goes out before the notes so the beat lands with them.
+    //an output that's still joining gets nothing until its start or continue
+    let on_16th = self.song.running() && self.song.ticks().is_multiple_of(6);
+    for voicer in self.outs.iter_mut() {
+      if voicer.clock == Clock::Joining && on_16th {
+        voicer.clock = Clock::On;
+        voicer.realtime(&self.song.join());
+      }
+      if voicer.clock == Clock::On {
+        voicer.realtime(&[song::CLOCK]);
+      }
+    }
+
+    //a stopped song keeps its place, nothing moves until it goes again
+    if !self.song.running() {
+      return Ok(());
+    }
+
+    match self.ramp.as_mut().map(|r|r.step()) {
+      Some(Some(bpm)) => self.tempo(bpm),
+      Some(None) => self.ramp = None,
+      None => ()
+    }
+
     //kill all the non-legato notes
     for voicer in self.outs.iter_mut() {
       voicer.pre_tick();
     }
 
-    for slot in self.slots.iter_mut() {
+    for (i,slot) in self.slots.iter_mut().enumerate() {
       slot.sync(self.sync_ticks);
       let out = self.outs.get_mut(slot.out);
-      let _ = slot.tick(out).inspect_err(|e|println!("slot output error: {:?}",e));
+      if let Err(e) = slot.tick(out,&self.key) {
+        let nm = slot.name.as_ref().map(|nm|format!(" ({})",nm)).unwrap_or_default();
+        println!("slot {}{} output error: {:?}",i,nm,e);
+      }
     }
 
     for voicer in self.outs.iter_mut() {
@@ -60,17 +142,88 @@ impl Sequencer {
     if self.sync_ticks == 96*4 {
       self.sync_ticks = 0;
     }
+    self.song.tick();
 
     Ok(())
   }
 
   pub fn kill_all(&mut self) {
+    self.song.stop();
     self.slots.iter_mut().for_each(|s|s.stop());
+    self.send_transport(&[song::STOP]);
     self.outs.iter_mut().for_each(|v|v.kill_all());
   }
 
-  pub fn wait(&mut self) {
-    self.timer.wait()
+  //true when it's time for the next tick. another box's clock can
+  //also start, stop or move the song while we wait for it
+  pub fn wait(&mut self) -> bool {
+    let msg = match &mut self.source {
+      Source::Internal(timer) => {
+        timer.wait();
+        return true;
+      },
+      Source::External(clock) => clock.next()
+    };
+
+    match msg {
+      Some(ClockMsg::Tick) => return true,
+      Some(ClockMsg::Start) => self.start(),
+      Some(ClockMsg::Stop) => self.pause(),
+      Some(ClockMsg::Continue) => self.resume(),
+      Some(ClockMsg::Position(sixteenths)) => self.locate(sixteenths*6),
+      None => ()
+    };
+    false
+  }
+
+  //transport messages only go to outputs that are already following our clock
+  fn send_transport(&mut self,msg:&[u8]) {
+    self.outs.iter_mut().filter(|v|v.clock == Clock::On).for_each(|v|v.realtime(msg));
+  }
+
+  //the whole song from the top, every slot goes back to its first step
+  pub fn start(&mut self) {
+    self.outs.iter_mut().for_each(|v|v.kill_all());
+    self.song.start();
+    self.sync_ticks = 0;
+    self.slots.iter_mut().for_each(|s|s.rewind());
+    self.send_transport(&[song::START]);
+  }
+
+  //everything keeps its place and the notes that are on get let go
+  pub fn pause(&mut self) {
+    if !self.song.running() {
+      return;
+    }
+
+    self.song.stop();
+    self.outs.iter_mut().for_each(|v|v.kill_all());
+    self.send_transport(&[song::STOP]);
+  }
+
+  pub fn resume(&mut self) {
+    if self.song.running() {
+      return;
+    }
+
+    self.song.resume();
+    self.send_transport(&[song::CONTINUE]);
+  }
+
+  //playing slots move as far as the song does, so they stay in time with each other
+  pub fn locate(&mut self,ticks:usize) {
+    self.outs.iter_mut().for_each(|v|v.kill_all());
+    let by = ticks as i64 - self.song.ticks() as i64;
+    self.song.locate(ticks);
+    self.sync_ticks = ticks % (96*4);
+    self.slots.iter_mut().for_each(|s|s.shift(by));
+    let pos = self.song.position();
+    self.send_transport(&pos);
+  }
+
+  //a bar is 4 beats of 24 ticks
+  pub fn locate_bar(&mut self,bar:usize) {
+    self.locate(bar*96);
   }
 
   pub fn assign_slot(&mut self, slot:usize,seq:Box<dyn Sequence>) {
@@ -79,10 +232,14 @@ impl Sequencer {
     }
   }
 
-  pub fn assign_slot_output(&mut self, slot:usize,out:usize) {
-    if let Some(s) = self.slots.get_mut(slot) {
-      s.out = out;
-    }
+  //the slots that are asked for and actually exist
+  fn picked(&mut self,slots:&lang::Slots) -> impl Iterator<Item=&mut Slot> {
+    let nums = slots.nums(self.slots.len());
+    self.slots.iter_mut().enumerate().filter(move |(i,_)|nums.contains(i)).map(|(_,s)|s)
+  }
+
+  pub fn assign_slot_output(&mut self,slots:&lang::Slots,out:usize) {
+    self.picked(slots).for_each(|s|s.out = out);
   }
 
   pub fn push_output(&mut self,v:Voicer) -> usize {
@@ -90,31 +247,53 @@ impl Sequencer {
     self.outs.len()
   }
 
-  pub fn set_sync(&mut self,slot:usize,sync_num:usize) {
-    if let Some(s) = self.slots.get_mut(slot) {
-      s.set_sync(sync_num);
-      self.sync_lp();
+  pub fn clock_out(&mut self,out:usize,on:bool) {
+    let Some(v) = self.outs.get_mut(out) else {
+      println!("there's no output {} to send clock from",out);
+      return;
+    };
+
+    match (on,v.clock) {
+      (true,Clock::Off) => v.clock = Clock::Joining,
+      //one that was still joining never got a start, so there's nothing to stop
+      (false,Clock::Joining) => v.clock = Clock::Off,
+      (false,Clock::On) => {
+        v.realtime(&[song::STOP]);
+        v.clock = Clock::Off;
+      },
+      _ => ()
     }
   }
+
+  pub fn set_sync(&mut self,slots:&lang::Slots,sync_num:usize) {
+    self.picked(slots).for_each(|s|s.set_sync(sync_num));
+    self.sync_lp();
+  }
   
-  pub fn set_div(&mut self,slot:usize,sync_num:usize) {
-    if let Some(s) = self.slots.get_mut(slot) {
-      s.set_div(sync_num);
-    }
+  pub fn set_div(&mut self,slots:&lang::Slots,div_num:usize) {
+    self.picked(slots).for_each(|s|s.set_div(div_num));
   }
 
-  pub fn play_slot(&mut self,slot:usize) {
-    if let Some(s) = self.slots.get_mut(slot) {
-      s.play();
-      self.sync_lp();
-    }
+  pub fn play_slots(&mut self,slots:&lang::Slots) {
+    self.picked(slots).for_each(|s|s.play());
+    self.sync_lp();
   }
 
-  pub fn stop_slot(&mut self,slot:usize) {
-    if let Some(s) = self.slots.get_mut(slot) {
-      s.stop();
-      self.sync_lp();
+  pub fn stop_slots(&mut self,slots:&lang::Slots) {
+    self.picked(slots).for_each(|s|s.stop());
+    self.sync_lp();
+  }
+
+  pub fn name_slot(&mut self,slot:usize,nm:String) {
+    if slot >= self.slots.len() {
+      return;
+    }
+
+    for s in self.slots.iter_mut().filter(|s|s.name.as_ref() == Some(&nm)) {
+      s.name = None;
     }
+    self.slots[slot].name = Some(nm);
+    self.sync_lp();
   }
 
   pub fn toggle_slot(&mut self,slot:usize) {
@@ -142,6 +321,7 @@ impl Sequencer {
 
       let _ = match s.state() {
         SlotState::Empty => lp.set_grid(row,col,Color::Off),
+        SlotState::Named => lp.set_grid(row,col,Color::Orange1),
         SlotState::Stopped => lp.set_grid(row,col,Color::Red3),
         SlotState::StopSync => lp.set_grid(row,col,Color::Red1),
         SlotState::Playing => lp.set_grid(row,col,Color::Green3),
@@ -3,7 +3,7 @@ use super::{
   voicer::Voicer
 };
 
-use lang::SeqNote;
+use lang::{Pitch,Key};
 
 #[derive(Debug,Default,Copy,Clone,PartialEq,Eq)]
 pub enum Transport {
@@ -16,6 +16,7 @@ pub enum Transport {
 
 pub struct Slot {
   pub out:usize,
+  pub name:Option<String>,
   current:Option<Box::<dyn Sequence>>,
   tpt:Transport,
   tick:usize,
@@ -24,10 +25,33 @@ pub struct Slot {
   sync_pt:usize
 }
 
-fn play_step(step:usize, tps:usize, seq:&mut dyn Sequence,v:&mut Voicer) -> Result<(),TickError> {
+//None when a scale degree lands outside of the midi range in this key
+fn pick(pitch:&mut Pitch,key:&Key) -> Option<u8> {
+  match pitch {
+    Pitch::Note(nn) => Some(*nn),
+    Pitch::Degree{deg,oct,shift} => key.resolve(*deg,*oct,*shift),
+    Pitch::Choose(ps) => {
+      let i = fastrand::usize(..ps.len());
+      pick(&mut ps[i],key)
+    },
+    Pitch::Cycle(ps,next) => {
+      let i = *next;
+      *next = (*next + 1) % ps.len();
+      pick(&mut ps[i],key)
+    }
+  }
+}
+
+//notes in a cut up step land on the nearest tick at or before their spot
+fn play_step(step:usize, tick:usize, tps:usize, key:&Key, seq:&mut dyn Sequence,v:&mut Voicer) -> Result<(),TickError> {
   if let Some(events) = seq.events(step) {
-    for sqn in events.iter() {
-      v.play_note(sqn.nn,sqn.vel,sqn.vel,sqn.duration * tps ,sqn.legato)
+    let due = events.iter_mut().filter(|n| n.sub.0 * tps / n.sub.1 == tick);
+
+    //chances get rolled fresh every time the step comes around
+    for sqn in due.filter(|n| n.prob >= 100 || fastrand::u8(0..100) < n.prob) {
+      let Some(nn) = pick(&mut sqn.pitch,key) else { continue };
+      let dur = (sqn.duration * tps / sqn.sub.1).max(1);
+      v.play_note(nn,sqn.vel,sqn.vel,dur,sqn.legato)
       .map_err(|_|TickError::Bad)?;
     }
   }
@@ -36,13 +60,11 @@ fn play_step(step:usize, tps:usize, seq:&mut dyn Sequence,v:&mut Voicer) -> Resu
 }
 
 impl Slot {
-  pub fn tick(&mut self,v:Option<&mut Voicer>) -> Result<(),TickError> {
+  pub fn tick(&mut self,v:Option<&mut Voicer>,key:&Key) -> Result<(),TickError> {
     if let (Some(seq),Transport::Play) = (&mut self.current,self.tpt) {
-      let err = if let (0,Some(voice)) = (self.tick,v) {
-        play_step(self.step,self.tps,seq.as_mut(),voice)
-      }
-      else {
-        Ok(())
+      let err = match v {
+        Some(voice) => play_step(self.step,self.tick,self.tps,key,seq.as_mut(),voice),
+        None => Ok(())
       };
   
       self.tick += 1;
@@ -73,6 +95,30 @@ impl Slot {
     }
   }
 
+  //moves a playing slot the same number of ticks as the song, forwards or back,
+  //at the div it has now. it keeps its own place against the song wherever
+  //its play or sync started it. stopped slots stay at the top for their next play
+  pub fn shift(&mut self,by:i64) {
+    if self.tpt != Transport::Play {
+      return;
+    }
+
+    let len = self.current.as_ref().map(|s|s.len()).unwrap_or(1).max(1);
+    let tps = self.tps.max(1);
+    let at = (self.step*tps + self.tick) as i64;
+    let to = (at + by).rem_euclid((len*tps) as i64) as usize;
+    self.tick = to % tps;
+    self.step = to / tps;
+  }
+
+  //back to the first step, only a playing slot can be anywhere else
+  pub fn rewind(&mut self) {
+    if self.tpt == Transport::Play {
+      self.tick = 0;
+      self.step = 0;
+    }
+  }
+
   pub fn assign(&mut self, seq:Box::<dyn Sequence>) {
     self.current = Some(seq);
     self.tick=0;
@@ -102,7 +148,10 @@ impl Slot {
 
   pub fn state(&self) -> SlotState {
     if self.current.is_none() {
-      return SlotState::Empty
+      return match self.name {
+        Some(_) => SlotState::Named,
+        None => SlotState::Empty
+      }
     };
 
     match self.tpt {
@@ -133,6 +182,7 @@ impl Default for Slot {
   fn default() -> Self {
     Self {
       out:0,
+      name:None,
       current:None,
       tpt:Transport::Stop,
       tick:0,
@@ -150,8 +200,86 @@ pub enum TickError {
 
 pub enum SlotState {
   Empty,
+  //nothing in it yet but it's got a name, so it's saved for something
+  Named,
   Stopped,
   StopSync,
   Playing,
   PlaySync
 }
+
+#[cfg(test)]
+mod tests {
+  use std::collections::HashMap;
+
+  use super::*;
+  use crate::sequence_types::MapSeq;
+
+  fn slot(len:usize,div:usize) -> Slot {
+    let mut s = Slot::default();
+    s.assign(Box::new(MapSeq::new(HashMap::new(),len)));
+    s.set_div(div);
+    s
+  }
+
+  fn ticks(s:&mut Slot,n:usize) {
+    for _ in 0..n {
+      let _ = s.tick(None,&Key::default());
+    }
+  }
+
+  #[test]
+  fn test_shift() {
+    //3 steps of 4 ticks goes round every 12 ticks
+    let mut s = slot(3,4);
+    s.play();
+    ticks(&mut s,5);
+    assert_eq!((s.step,s.tick),(1,1));
+
+    s.shift(10);
+    assert_eq!((s.step,s.tick),(0,3));
+    s.shift(-4);
+    assert_eq!((s.step,s.tick),(2,3));
+    s.shift(-12*40);
+    assert_eq!((s.step,s.tick),(2,3));
+
+    //a div change counts from where it is now
+    ticks(&mut s,1);
+    s.set_div(2);
+    s.shift(3);
+    assert_eq!((s.step,s.tick),(1,1));
+
+    //one that started later keeps the same gap
+    let (mut a,mut b) = (slot(4,3),slot(4,3));
+    a.play();
+    ticks(&mut a,5);
+    b.play();
+    ticks(&mut a,2);
+    ticks(&mut b,2);
+    for by in [7,-30,100] {
+      a.shift(by);
+      b.shift(by);
+      let gap = (a.step*3 + a.tick + 12 - b.step*3 - b.tick) % 12;
+      assert_eq!(gap,5);
+    }
+
+    //stopped and waiting slots stay at the top
+    let mut s = slot(3,4);
+    s.shift(7);
+    assert_eq!((s.step,s.tick),(0,0));
+    s.set_sync(96);
+    s.toggle();
+    s.shift(7);
+    s.rewind();
+    assert_eq!((s.step,s.tick),(0,0));
+  }
+
+  #[test]
+  fn test_rewind() {
+    let mut s = slot(3,4);
+    s.play();
+    ticks(&mut s,7);
+    s.rewind();
+    assert_eq!((s.step,s.tick),(0,0));
+  }
+}
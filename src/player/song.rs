@@ -0,0 +1,109 @@
+//midi realtime messages, they don't have a channel
+pub const CLOCK : u8 = 0xF8;
+pub const START : u8 = 0xFA;
+pub const CONTINUE : u8 = 0xFB;
+pub const STOP : u8 = 0xFC;
+
+//where the whole set is up to, apart from any one slot playing or not.
+//it counts 24 ticks to a beat, same as midi clock
+pub struct Song {
+  running:bool,
+  ticks:usize
+}
+
+impl Song {
+  pub fn new() -> Self {
+    Self {
+      running:true,
+      ticks:0
+    }
+  }
+
+  pub fn running(&self) -> bool {
+    self.running
+  }
+
+  pub fn ticks(&self) -> usize {
+    self.ticks
+  }
+
+  pub fn tick(&mut self) {
+    if self.running {
+      self.ticks += 1;
+    }
+  }
+
+  pub fn start(&mut self) {
+    self.running = true;
+    self.ticks = 0;
+  }
+
+  pub fn stop(&mut self) {
+    self.running = false;
+  }
+
+  pub fn resume(&mut self) {
+    self.running = true;
+  }
+
+  pub fn locate(&mut self,ticks:usize) {
+    self.ticks = ticks;
+  }
+
+  //song position counts 16th notes (6 ticks) and only has 14 bits
+  pub fn position(&self) -> [u8;3] {
+    let sixteenths = (self.ticks/6).min(0x3FFF);
+    [0xF2,(sixteenths & 0x7F) as u8,(sixteenths >> 7) as u8]
+  }
+
+  //a device joining at the very start gets a start, anywhere else it gets told where we are
+  pub fn join(&self) -> Vec<u8> {
+    match self.ticks {
+      0 => vec![START],
+      _ => {
+        let mut msg = self.position().to_vec();
+        msg.push(CONTINUE);
+        msg
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_position() {
+    let mut song = Song::new();
+    assert_eq!(song.position(),[0xF2,0,0]);
+
+    //200 16ths is 0b1_1001000, low 7 bits first
+    song.locate(200*6 + 5);
+    assert_eq!(song.position(),[0xF2,0x48,0x01]);
+
+    //14 bits is as far as it goes
+    song.locate(0x3FFF*6);
+    assert_eq!(song.position(),[0xF2,0x7F,0x7F]);
+    song.locate(0x4000*6 + 100);
+    assert_eq!(song.position(),[0xF2,0x7F,0x7F]);
+  }
+
+  #[test]
+  fn test_join() {
+    let mut song = Song::new();
+    assert_eq!(song.join(),[START]);
+
+    song.tick();
+    song.stop();
+    song.tick();
+    assert_eq!(song.ticks(),1);
+
+    song.locate(96);
+    assert_eq!(song.join(),[0xF2,16,0,CONTINUE]);
+
+    song.start();
+    assert!(song.running());
+    assert_eq!(song.join(),[START]);
+  }
+}
@@ -1,21 +1,294 @@
+use std::time::{Duration,Instant};
+
+use super::clock_in::ClockIn;
+
+//where the ticks come from, our own timer or another box's clock
+pub enum Source {
+  Internal(TimeBuddy),
+  External(ClockIn)
+}
+
+//a beat is 24 ticks, so at 1 bpm a tick is 60s/24
+const NANOS_AT_1BPM : f64 = 2_500_000_000.0;
+
+//ramps go by the bar, 4 beats of 24 ticks
+const BAR_TICKS : usize = 96;
+
+//sleeping is only good to a millisecond or so, the last bit gets spun out
+const SPIN : Duration = Duration::from_millis(1);
+
+//this far behind something went badly wrong (like the laptop going to sleep),
+//better to start the timeline again than rush out a pile of ticks to catch up
+const GIVE_UP : Duration = Duration::from_secs(1);
+
+//every tick is due at a spot worked out from a fixed start and how many
+//ticks it's been since then, so rounding never piles up into drift
 pub struct TimeBuddy {
-  timer:adi_clock::Timer
+  start:Instant,
+  ticks:u64,
+  bpm:f64,
+  stats:Stats
 }
 
-//this goes from BPM to seconds per tick (1/24 of a beat)
-const fn bpm_to_timing(bpm:usize) -> f32 {
-  60.0/(24.0 * (bpm as f32))
+//when tick n is due if the count started at start
+fn due(start:Instant,bpm:f64,n:u64) -> Instant {
+  start + Duration::from_nanos((n as f64 * NANOS_AT_1BPM / bpm) as u64)
 }
 
 impl TimeBuddy {
-  pub fn new(bpm:usize) -> Self {
-    let timer = adi_clock::Timer::new(bpm_to_timing(bpm));
+  pub fn new(bpm:f64) -> Self {
+    Self {
+      start:Instant::now(),
+      ticks:0,
+      bpm,
+      stats:Stats::default()
+    }
+  }
+
+  //the new tempo counts from the last tick, so the beat carries on from where it was
+  pub fn set_bpm(&mut self,bpm:f64) {
+    self.start = due(self.start,self.bpm,self.ticks);
+    self.ticks = 0;
+    self.bpm = bpm;
+  }
+
+  pub fn wait(&mut self) {
+    let due = due(self.start,self.bpm,self.ticks + 1);
+    let left = due.saturating_duration_since(Instant::now());
+    if left > SPIN {
+      std::thread::sleep(left - SPIN);
+    }
+    while Instant::now() < due {
+      std::hint::spin_loop();
+    }
+
+    self.ticked(Instant::now());
+  }
+
+  //the next tick went out at now, however late that was
+  fn ticked(&mut self,now:Instant) {
+    let late = now.saturating_duration_since(due(self.start,self.bpm,self.ticks + 1));
+    self.ticks += 1;
+    if late > GIVE_UP {
+      self.start = now;
+      self.ticks = 0;
+      self.stats.restarts += 1;
+    }
+    self.stats.add(late);
+  }
+
+  pub fn stats(&self) -> &Stats {
+    &self.stats
+  }
+}
+
+//a tempo change spread out over some bars, every tick gets a bit closer
+pub struct Ramp {
+  from:f64,
+  to:f64,
+  ticks:usize,
+  done:usize
+}
+
+impl Ramp {
+  pub fn new(from:f64,to:f64,bars:usize) -> Self {
     Self {
-      timer
+      from,
+      to,
+      ticks:bars*BAR_TICKS,
+      done:0
+    }
+  }
+
+  //the bpm for the next tick, None once it's got there
+  pub fn step(&mut self) -> Option<f64> {
+    if self.done >= self.ticks {
+      return None;
+    }
+
+    //the last step is exactly where it was going, rounding can't leave it a hair off
+    self.done += 1;
+    match self.done == self.ticks {
+      true => Some(self.to),
+      false => Some(self.from + (self.to - self.from) * (self.done as f64 / self.ticks as f64))
     }
   }
+}
+
+//taps further apart than this are a new go at it, not a really slow tempo
+const TAP_GAP : Duration = Duration::from_secs(2);
+
+//the last few taps get averaged so one sloppy one doesn't throw it off
+const TAPS : usize = 5;
 
-  pub fn wait(& mut self) {
-    self.timer.wait();
+#[derive(Default)]
+pub struct Tapper {
+  taps:Vec<Instant>
+}
+
+impl Tapper {
+  //the bpm from the taps so far, it takes two to get one
+  pub fn tap(&mut self,at:Instant) -> Option<f64> {
+    if self.taps.last().is_some_and(|t|at.saturating_duration_since(*t) > TAP_GAP) {
+      self.taps.clear();
+    }
+
+    self.taps.push(at);
+    if self.taps.len() > TAPS {
+      self.taps.remove(0);
+    }
+
+    let (first,last) = (self.taps.first()?,self.taps.last()?);
+    let each = last.saturating_duration_since(*first).as_secs_f64() / (self.taps.len() - 1) as f64;
+    (each > 0.0).then(||60.0 / each)
+  }
+}
+
+//how late the ticks have been. jitter is how much that wobbles
+#[derive(Default)]
+pub struct Stats {
+  ticks:u64,
+  total:f64,
+  total_sq:f64,
+  worst:Duration,
+  last_late:Duration,
+  restarts:usize
+}
+
+impl Stats {
+  fn add(&mut self,late:Duration) {
+    let ms = late.as_secs_f64() * 1000.0;
+    self.ticks += 1;
+    self.total += ms;
+    self.total_sq += ms*ms;
+    self.worst = self.worst.max(late);
+    self.last_late = late;
+  }
+}
+
+impl std::fmt::Display for Stats {
+  fn fmt(&self,f:&mut std::fmt::Formatter) -> Result<(),std::fmt::Error> {
+    if self.ticks == 0 {
+      return write!(f,"no ticks yet");
+    }
+
+    let n = self.ticks as f64;
+    let mean = self.total / n;
+    let jitter = (self.total_sq / n - mean*mean).max(0.0).sqrt();
+    writeln!(f,"{} ticks, late by {:.3}ms on average",self.ticks,mean)?;
+    writeln!(f,"jitter {:.3}ms, worst {:.3}ms",jitter,self.worst.as_secs_f64() * 1000.0)?;
+    write!(f,"last tick late by {:.3}ms, timeline restarted {} times",self.last_late.as_secs_f64() * 1000.0,self.restarts)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_due() {
+    let start = Instant::now();
+    //120 bpm is 48 ticks a second
+    assert_eq!(due(start,120.0,0),start);
+    assert_eq!(due(start,120.0,48),start + Duration::from_secs(1));
+    assert_eq!(due(start,60.0,1) - start,Duration::from_nanos(41_666_666));
+    //going by the count keeps it exact, adding up that many rounded ticks wouldn't
+    assert_eq!(due(start,92.5,92500) - start,Duration::from_secs(1000*60/24));
+  }
+
+  #[test]
+  fn test_set_bpm() {
+    let mut timer = TimeBuddy::new(120.0);
+    let start = timer.start;
+    for n in 1..=24 {
+      timer.ticked(due(start,120.0,n));
+    }
+
+    //half a second in, the count starts again from the last tick
+    timer.set_bpm(60.0);
+    assert_eq!(timer.start,start + Duration::from_millis(500));
+    assert_eq!(timer.ticks,0);
+    assert_eq!(due(timer.start,timer.bpm,24),start + Duration::from_millis(1500));
+  }
+
+  #[test]
+  fn test_give_up() {
+    let mut timer = TimeBuddy::new(120.0);
+    let start = timer.start;
+    timer.ticked(due(start,120.0,1) + Duration::from_millis(2));
+    assert_eq!((timer.start,timer.ticks),(start,1));
+
+    //way behind, the timeline starts again from now
+    let now = due(start,120.0,2) + GIVE_UP * 3;
+    timer.ticked(now);
+    assert_eq!((timer.start,timer.ticks),(now,0));
+
+    let stats = timer.stats();
+    assert_eq!((stats.ticks,stats.restarts),(2,1));
+    assert_eq!(stats.worst,GIVE_UP * 3);
+  }
+
+  #[test]
+  fn test_ramp() {
+    let steps : Vec<f64> = std::iter::from_fn({
+      let mut ramp = Ramp::new(120.0,92.5,2);
+      move||ramp.step()
+    }).collect();
+
+    assert_eq!(steps.len(),2*BAR_TICKS);
+    assert_eq!(steps[BAR_TICKS - 1],106.25);
+    assert_eq!(steps.last(),Some(&92.5));
+    assert!(steps.windows(2).all(|w|w[1] < w[0]));
+
+    //done is done, it doesn't go round again
+    let mut ramp = Ramp::new(93.1,140.7,1);
+    let last = (0..BAR_TICKS).map(|_|ramp.step()).last().flatten();
+    assert_eq!(last,Some(140.7));
+    assert_eq!(ramp.step(),None);
+    assert_eq!(ramp.step(),None);
+
+    //no bars means no steps, the sequencer just sets the bpm for that
+    assert_eq!(Ramp::new(120.0,60.0,0).step(),None);
+  }
+
+  #[test]
+  fn test_tapper() {
+    let t0 = Instant::now();
+    let at = |ms| t0 + Duration::from_millis(ms);
+
+    let mut taps = Tapper::default();
+    assert_eq!(taps.tap(at(0)),None);
+    assert_eq!(taps.tap(at(500)),Some(120.0));
+
+    //a long gap starts over, so that tap on its own doesn't give a bpm
+    assert_eq!(taps.tap(at(3000)),None);
+    assert_eq!(taps.tap(at(3600)),Some(100.0));
+
+    //only the last 5 count, the 3000 one has dropped off by the end
+    for ms in [4200,4800,5400] {
+      assert_eq!(taps.tap(at(ms)),Some(100.0));
+    }
+    assert_eq!(taps.tap(at(5900)),Some(60.0 / 0.575));
+    assert_eq!(taps.taps.len(),TAPS);
+
+    //two taps at once can't be a tempo
+    let mut taps = Tapper::default();
+    taps.tap(at(0));
+    assert_eq!(taps.tap(at(0)),None);
+  }
+
+  #[test]
+  fn test_stats() {
+    assert_eq!(Stats::default().to_string(),"no ticks yet");
+
+    let mut stats = Stats::default();
+    for ms in [1,3,1,3] {
+      stats.add(Duration::from_millis(ms));
+    }
+    assert_eq!(stats.to_string(),[
+      "4 ticks, late by 2.000ms on average",
+      "jitter 1.000ms, worst 3.000ms",
+      "last tick late by 3.000ms, timeline restarted 0 times"
+    ].join("\n"));
   }
 }
@@ -1,9 +1,19 @@
 use midir::MidiOutputConnection as Conn;
 
+//an output that turns its clock on waits for the next 16th to join in,
+//song position can't point anywhere finer than that
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+pub enum Clock {
+  Off,
+  Joining,
+  On
+}
+
 pub struct Voicer {
   conn:midir::MidiOutputConnection,
   channel:u8,
-  buffer:Vec<(u8,u8,usize,bool)>
+  buffer:Vec<(u8,u8,usize,bool)>,
+  pub clock:Clock
 }
 
 impl Voicer {
@@ -11,10 +21,18 @@ impl Voicer {
     Ok(Self{
       conn,
       channel,
-      buffer:Vec::with_capacity(128)
+      buffer:Vec::with_capacity(128),
+      clock:Clock::Off
     })
   }
 
+  //clock and transport messages, they only go out if this output sends clock
+  pub fn realtime(&mut self,msg:&[u8]) {
+    if self.clock != Clock::Off {
+      let _ = self.conn.send(msg).inspect_err(|e|println!("clock send failed: {}",e));
+    }
+  }
+
   pub fn pre_tick(&mut self) {
     self.buffer.iter_mut().for_each(|msg| {
       let (nn,ov,dur,leg) = msg;
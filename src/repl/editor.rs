@@ -43,12 +43,21 @@ pub fn lp<T:lang::Runtime>(ed:&mut Editor,terp:&mut lang::Env<T>) -> Result<(),E
     Ok(_) => {
       println!("cooool");
     }
-    Err(e) => {
-      e.file_path().inspect(|p|println!("in included file: {}",p));
-      println!("got an error on line {} : {}",e.line_num(),e.msg());
-      println!("{}{}{}",e.pre_txt(),e.txt().bright_red(),e.post_txt());
-      println!();
-    }
+    Err(errs) => print_errors(&errs)
   }
   Ok(())
 }
+
+pub fn print_errors(errs:&[lang::Error]) {
+  if errs.len() > 1 {
+    println!("got {} errors",errs.len());
+  }
+
+  for e in errs.iter() {
+    e.file_path().inspect(|p|println!("in included file: {}",p));
+    println!("got an error on line {} [{}]: {}",e.line_num(),e.code(),e.msg());
+    println!("{}{}{}",e.pre_txt(),e.txt().bright_red(),e.post_txt());
+    e.help().inspect(|h|println!("{} {}","help:".bright_blue(),h));
+    println!();
+  }
+}
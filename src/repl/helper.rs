@@ -5,23 +5,26 @@ use rustyline::{
   Helper,
   Completer,
   Hinter,
-  Validator,
   highlight::{
     CmdKind, 
     Highlighter, 
     MatchingBracketHighlighter
   },
   hint::HistoryHinter,
-  validate::MatchingBracketValidator,
+  validate::{
+    MatchingBracketValidator,
+    ValidationContext,
+    ValidationResult,
+    Validator
+  },
   completion::FilenameCompleter,
 };
 
 
-#[derive(Helper, Completer, Hinter, Validator)]
+#[derive(Helper, Completer, Hinter)]
 pub struct RepHelp {
   #[rustyline(Completer)]
   completer: FilenameCompleter,
-  #[rustyline(Validator)]
   validator: MatchingBracketValidator,
   #[rustyline(Hinter)]
   hinter: HistoryHinter,
@@ -51,6 +54,17 @@ impl Highlighter for RepHelp {
   }
 }
 
+impl Validator for RepHelp {
+  fn validate(&self, ctx: &mut ValidationContext) -> rustyline::Result<ValidationResult> {
+    //a \ at the end of the line means it carries on to the next one
+    if ctx.input().trim_end_matches([' ','\t']).ends_with('\\') {
+      return Ok(ValidationResult::Incomplete)
+    }
+
+    self.validator.validate(ctx)
+  }
+}
+
 impl RepHelp {
   pub fn new() -> Self {
     Self {    
@@ -2,7 +2,7 @@ use std::collections::HashMap;
 use lang::SeqNote;
 
 pub trait Sequence:Send {
-  fn events(&mut self,step:usize) -> Option<&[SeqNote]>;
+  fn events(&mut self,step:usize) -> Option<&mut [SeqNote]>;
   fn len(&self) -> usize;
 }
 
@@ -26,8 +26,8 @@ impl Sequence for MapSeq {
     self.len
   }
 
-  fn events(&mut self,step:usize) -> Option<&[SeqNote]> {
-    self.notes.get(&step).map(|v|&v[..])
+  fn events(&mut self,step:usize) -> Option<&mut [SeqNote]> {
+    self.notes.get_mut(&step).map(|v|&mut v[..])
   }
 }
 